@@ -1,6 +1,365 @@
-enum FacingDirection {
+use crate::game::effects::Invulnerable;
+use crate::player::{MainPlayer, Player};
+use crate::GameState;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+use std::fmt::Display;
+
+/// Sheet used for every player until characters can be picked
+const PLAYER_SHEET: &str = "slime.anim.ron";
+/// Remote players keep their move clip this long after their last position update
+const REMOTE_MOVE_GRACE: f32 = 0.15;
+/// Position jumps larger than this are wraparounds, not movement
+const MAX_MOVE_DELTA: f32 = 100.0;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<AnimationSheet>()
+            .init_asset_loader::<AnimationSheetLoader>()
+            .add_systems(Startup, load_player_sheet)
+            .add_systems(
+                Update,
+                (
+                    apply_sheet_changes,
+                    (main_player_facing, remote_player_facing),
+                    select_clip,
+                    animate_sprites,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+    }
+}
+
+/// Sprite sheet with named animation clips, loaded from `*.anim.ron` files
+#[derive(Asset, TypePath, Debug)]
+pub struct AnimationSheet {
+    pub texture: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    pub columns: usize,
+    pub clips: HashMap<String, ClipDefinition>,
+}
+
+#[derive(Deserialize)]
+struct AnimationSheetDefinition {
+    /// Texture path, relative to the assets folder
+    texture: String,
+    tile_size: (u32, u32),
+    columns: u32,
+    rows: u32,
+    clips: HashMap<String, ClipDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClipDefinition {
+    pub rows: FacingRows,
+    /// First and last frame of the clip within its row, inclusive
+    pub frames: (usize, usize),
+    pub fps: f32,
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+/// Atlas row of a clip for each facing, `side` faces right and is flipped for `Left`
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct FacingRows {
+    pub down: usize,
+    pub side: usize,
+    pub up: usize,
+}
+
+impl FacingRows {
+    fn row(&self, facing: FacingDirection) -> usize {
+        match facing {
+            FacingDirection::Down => self.down,
+            FacingDirection::Left | FacingDirection::Right => self.side,
+            FacingDirection::Up => self.up,
+        }
+    }
+}
+
+#[derive(Default)]
+struct AnimationSheetLoader;
+
+#[derive(Debug)]
+pub enum AnimationSheetLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl Display for AnimationSheetLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnimationSheetLoaderError::Io(e) => write!(f, "IO error: {}", e),
+            AnimationSheetLoaderError::Ron(e) => write!(f, "RON error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AnimationSheetLoaderError {}
+
+impl From<std::io::Error> for AnimationSheetLoaderError {
+    fn from(e: std::io::Error) -> Self {
+        AnimationSheetLoaderError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for AnimationSheetLoaderError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        AnimationSheetLoaderError::Ron(e)
+    }
+}
+
+impl AssetLoader for AnimationSheetLoader {
+    type Asset = AnimationSheet;
+    type Settings = ();
+    type Error = AnimationSheetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let definition = ron::de::from_bytes::<AnimationSheetDefinition>(&bytes)?;
+
+        let layout = TextureAtlasLayout::from_grid(
+            UVec2::new(definition.tile_size.0, definition.tile_size.1),
+            definition.columns,
+            definition.rows,
+            None,
+            None,
+        );
+        Ok(AnimationSheet {
+            texture: load_context.load(definition.texture),
+            layout: load_context.add_labeled_asset("layout".to_string(), layout),
+            columns: definition.columns as usize,
+            clips: definition.clips,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+/// Sheet shared by all players
+#[derive(Resource)]
+pub struct PlayerAnimationSheet(pub Handle<AnimationSheet>);
+
+fn load_player_sheet(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PlayerAnimationSheet(asset_server.load(PLAYER_SHEET)));
+}
+
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FacingDirection {
     Left,
     Right,
     Up,
+    #[default]
     Down,
 }
+
+impl FacingDirection {
+    /// Facing for a movement direction, `None` if not moving
+    pub fn from_direction(direction: Vec2) -> Option<Self> {
+        if direction.length_squared() <= f32::EPSILON {
+            return None;
+        }
+        let facing = if direction.x.abs() > direction.y.abs() {
+            if direction.x > 0.0 {
+                FacingDirection::Right
+            } else {
+                FacingDirection::Left
+            }
+        } else if direction.y > 0.0 {
+            FacingDirection::Up
+        } else {
+            FacingDirection::Down
+        };
+        Some(facing)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationClip {
+    #[default]
+    Idle,
+    Move,
+    Tagged,
+}
+
+impl AnimationClip {
+    /// Name of the clip in animation sheets
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimationClip::Idle => "idle",
+            AnimationClip::Move => "move",
+            AnimationClip::Tagged => "tagged",
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct SpriteAnimation {
+    pub sheet: Handle<AnimationSheet>,
+    pub clip: AnimationClip,
+    pub moving: bool,
+    frame: usize,
+    elapsed: f32,
+}
+
+impl SpriteAnimation {
+    pub fn new(sheet: Handle<AnimationSheet>) -> Self {
+        Self {
+            sheet,
+            clip: AnimationClip::Idle,
+            moving: false,
+            frame: 0,
+            elapsed: 0.0,
+        }
+    }
+    fn play(&mut self, clip: AnimationClip) {
+        if self.clip == clip {
+            return;
+        }
+        self.clip = clip;
+        self.frame = 0;
+        self.elapsed = 0.0;
+    }
+}
+
+/// Tracks position deltas of players whose velocity we don't simulate
+#[derive(Component, Default)]
+pub struct RemoteMotion {
+    last_position: Option<Vec2>,
+    since_moved: f32,
+}
+
+/// Points sprites at the new texture and layout when a sheet is (re)loaded
+fn apply_sheet_changes(
+    mut events: EventReader<AssetEvent<AnimationSheet>>,
+    sheets: Res<Assets<AnimationSheet>>,
+    mut query: Query<(&SpriteAnimation, &mut Handle<Image>, &mut TextureAtlas)>,
+) {
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        let Some(sheet) = sheets.get(*id) else {
+            continue;
+        };
+        for (animation, mut texture, mut atlas) in query.iter_mut() {
+            if animation.sheet.id() == *id {
+                *texture = sheet.texture.clone();
+                atlas.layout = sheet.layout.clone();
+            }
+        }
+    }
+}
+
+fn main_player_facing(
+    mut query: Query<(&Velocity, &mut FacingDirection, &mut SpriteAnimation), With<MainPlayer>>,
+) {
+    for (velocity, mut facing, mut animation) in query.iter_mut() {
+        let new_facing = FacingDirection::from_direction(velocity.linvel);
+        if let Some(new_facing) = new_facing {
+            facing.set_if_neq(new_facing);
+        }
+        animation.moving = new_facing.is_some();
+    }
+}
+
+fn remote_player_facing(
+    time: Res<Time>,
+    mut query: Query<
+        (
+            &Transform,
+            &mut RemoteMotion,
+            &mut FacingDirection,
+            &mut SpriteAnimation,
+        ),
+        Without<MainPlayer>,
+    >,
+) {
+    for (transform, mut motion, mut facing, mut animation) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let delta = motion
+            .last_position
+            .map_or(Vec2::ZERO, |last| position - last);
+        motion.last_position = Some(position);
+
+        match FacingDirection::from_direction(delta) {
+            Some(new_facing) if delta.length() < MAX_MOVE_DELTA => {
+                facing.set_if_neq(new_facing);
+                motion.since_moved = 0.0;
+            }
+            _ => motion.since_moved += time.delta_seconds(),
+        }
+        animation.moving = motion.since_moved < REMOTE_MOVE_GRACE;
+    }
+}
+
+fn select_clip(mut query: Query<(&mut SpriteAnimation, Has<Invulnerable>), With<Player>>) {
+    for (mut animation, tagged) in query.iter_mut() {
+        let clip = if tagged {
+            AnimationClip::Tagged
+        } else if animation.moving {
+            AnimationClip::Move
+        } else {
+            AnimationClip::Idle
+        };
+        animation.play(clip);
+    }
+}
+
+fn animate_sprites(
+    time: Res<Time>,
+    sheets: Res<Assets<AnimationSheet>>,
+    mut query: Query<(
+        &mut SpriteAnimation,
+        &FacingDirection,
+        &mut TextureAtlas,
+        &mut Sprite,
+    )>,
+) {
+    for (mut animation, facing, mut atlas, mut sprite) in query.iter_mut() {
+        let Some(sheet) = sheets.get(&animation.sheet) else {
+            continue;
+        };
+        let Some(clip) = sheet.clips.get(animation.clip.name()) else {
+            continue;
+        };
+        let (first, last) = clip.frames;
+        let frame_count = last.saturating_sub(first) + 1;
+
+        animation.elapsed += time.delta_seconds();
+        let frame_duration = 1.0 / clip.fps.max(f32::EPSILON);
+        while animation.elapsed >= frame_duration {
+            animation.elapsed -= frame_duration;
+            animation.frame += 1;
+        }
+        if clip.looping {
+            animation.frame %= frame_count;
+        } else {
+            animation.frame = animation.frame.min(frame_count - 1);
+        }
+
+        let row = clip.rows.row(*facing);
+        atlas.index = row * sheet.columns + first + animation.frame;
+        sprite.flip_x = *facing == FacingDirection::Left;
+    }
+}
@@ -0,0 +1,201 @@
+use crate::game::effects::PlayerTaggedEvent;
+use crate::game::{Round, RoundEndEvent};
+use crate::menu::MenuState;
+use crate::player::{MainPlayer, Player};
+use crate::settings::Settings;
+use crate::GameState;
+use bevy::audio::{PlaybackMode, Volume};
+use bevy::prelude::*;
+
+/// Last seconds of a round that get a countdown beep
+const COUNTDOWN_SECS: u32 = 5;
+/// Distance between the listener's ears, in world units
+pub const LISTENER_EAR_GAP: f32 = 64.0;
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_sounds)
+            .observe(on_player_tagged)
+            .observe(on_round_end)
+            .add_systems(OnEnter(GameState::Menu), play_music(MusicTrack::Menu))
+            .add_systems(OnEnter(GameState::Game), play_music(MusicTrack::Game))
+            .add_systems(OnExit(GameState::Menu), stop_music)
+            .add_systems(OnExit(GameState::Game), stop_music)
+            .add_systems(OnEnter(MenuState::Lobby), play_lobby_join)
+            .add_systems(
+                Update,
+                (
+                    play_button_clicks,
+                    play_countdown.run_if(in_state(GameState::Game)),
+                    apply_volume.run_if(resource_changed::<Settings>),
+                ),
+            );
+    }
+}
+
+#[derive(Resource)]
+struct Sounds {
+    tag: Handle<AudioSource>,
+    tagged: Handle<AudioSource>,
+    countdown: Handle<AudioSource>,
+    round_end: Handle<AudioSource>,
+    click: Handle<AudioSource>,
+    join: Handle<AudioSource>,
+    menu_music: Handle<AudioSource>,
+    game_music: Handle<AudioSource>,
+}
+
+#[derive(Clone, Copy)]
+enum MusicTrack {
+    Menu,
+    Game,
+}
+
+#[derive(Component)]
+struct Music;
+
+fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Sounds {
+        tag: asset_server.load("audio/tag.wav"),
+        tagged: asset_server.load("audio/tagged.wav"),
+        countdown: asset_server.load("audio/countdown.wav"),
+        round_end: asset_server.load("audio/round_end.wav"),
+        click: asset_server.load("audio/click.wav"),
+        join: asset_server.load("audio/join.wav"),
+        menu_music: asset_server.load("audio/menu_music.wav"),
+        game_music: asset_server.load("audio/game_music.wav"),
+    });
+}
+
+/// Plays a sound effect, positioned in the world when `position` is set
+fn play_effect(
+    commands: &mut Commands,
+    settings: &Settings,
+    source: &Handle<AudioSource>,
+    position: Option<Vec3>,
+) {
+    let playback = PlaybackSettings::DESPAWN.with_volume(Volume::new(settings.effects_volume));
+    match position {
+        Some(position) => commands.spawn((
+            AudioBundle {
+                source: source.clone(),
+                settings: playback.with_spatial(true),
+            },
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+        )),
+        None => commands.spawn(AudioBundle {
+            source: source.clone(),
+            settings: playback,
+        }),
+    };
+}
+
+fn play_music(track: MusicTrack) -> impl Fn(Commands, Res<Sounds>, Res<Settings>) {
+    move |mut commands: Commands, sounds: Res<Sounds>, settings: Res<Settings>| {
+        let source = match track {
+            MusicTrack::Menu => sounds.menu_music.clone(),
+            MusicTrack::Game => sounds.game_music.clone(),
+        };
+        commands.spawn((
+            AudioBundle {
+                source,
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Loop,
+                    volume: Volume::new(settings.music_volume),
+                    ..default()
+                },
+            },
+            Music,
+        ));
+    }
+}
+
+fn stop_music(mut commands: Commands, music: Query<Entity, With<Music>>) {
+    for entity in music.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Master volume scales every new sound, music already playing is updated in place
+fn apply_volume(
+    settings: Res<Settings>,
+    mut global_volume: ResMut<GlobalVolume>,
+    music: Query<&AudioSink, With<Music>>,
+) {
+    global_volume.volume = Volume::new(settings.master_volume);
+    for sink in music.iter() {
+        sink.set_volume(settings.master_volume * settings.music_volume);
+    }
+}
+
+fn on_player_tagged(
+    trigger: Trigger<PlayerTaggedEvent>,
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    settings: Res<Settings>,
+    players: Query<(&Player, &Transform, Has<MainPlayer>)>,
+) {
+    let Some((_, transform, is_main)) = players
+        .iter()
+        .find(|(player, _, _)| player.id == trigger.event().it)
+    else {
+        return;
+    };
+    if is_main {
+        play_effect(&mut commands, &settings, &sounds.tagged, None);
+    } else {
+        play_effect(
+            &mut commands,
+            &settings,
+            &sounds.tag,
+            Some(transform.translation),
+        );
+    }
+}
+
+fn on_round_end(
+    _trigger: Trigger<RoundEndEvent>,
+    mut commands: Commands,
+    sounds: Res<Sounds>,
+    settings: Res<Settings>,
+) {
+    play_effect(&mut commands, &settings, &sounds.round_end, None);
+}
+
+fn play_countdown(
+    round: Res<Round>,
+    sounds: Res<Sounds>,
+    settings: Res<Settings>,
+    mut last_second: Local<Option<u32>>,
+    mut commands: Commands,
+) {
+    if round.timer.is_none() {
+        *last_second = None;
+        return;
+    }
+    let second = round.remaining_secs().ceil() as u32;
+    if second == 0 || second > COUNTDOWN_SECS || *last_second == Some(second) {
+        return;
+    }
+    *last_second = Some(second);
+    play_effect(&mut commands, &settings, &sounds.countdown, None);
+}
+
+fn play_button_clicks(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+    sounds: Res<Sounds>,
+    settings: Res<Settings>,
+    mut commands: Commands,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            play_effect(&mut commands, &settings, &sounds.click, None);
+        }
+    }
+}
+
+fn play_lobby_join(mut commands: Commands, sounds: Res<Sounds>, settings: Res<Settings>) {
+    play_effect(&mut commands, &settings, &sounds.join, None);
+}
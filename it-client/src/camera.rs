@@ -1,13 +1,157 @@
+use crate::audio::LISTENER_EAR_GAP;
+use crate::game::Arena;
+use crate::input::{Action, ActionState};
+use crate::player::{MainPlayer, Player};
+use crate::GameState;
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_camera);
+        app.init_resource::<CameraSettings>()
+            .add_systems(Startup, setup_camera)
+            .add_systems(
+                Update,
+                (toggle_camera_mode, update_camera)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), reset_camera);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Track the main player
+    #[default]
+    Follow,
+    /// Zoom out so every player is on screen
+    ShowEveryone,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct CameraSettings {
+    pub mode: CameraMode,
+    /// Area around the camera center the followed player can move in without the camera moving
+    pub deadzone: Vec2,
+    /// How fast the camera catches up, higher is snappier
+    pub lerp_speed: f32,
+    /// World units visible vertically when following the main player
+    pub follow_height: f32,
+    /// Margin kept around players when showing everyone
+    pub fit_padding: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::Follow,
+            deadzone: Vec2::new(80.0, 60.0),
+            lerp_speed: 6.0,
+            follow_height: 480.0,
+            fit_padding: 96.0,
+        }
     }
 }
 
 fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
+    commands.spawn((
+        Camera2dBundle::default(),
+        SpatialListener::new(LISTENER_EAR_GAP),
+    ));
+}
+
+fn toggle_camera_mode(actions: Res<ActionState>, mut settings: ResMut<CameraSettings>) {
+    if actions.just_pressed(Action::CameraMode) {
+        settings.mode = match settings.mode {
+            CameraMode::Follow => CameraMode::ShowEveryone,
+            CameraMode::ShowEveryone => CameraMode::Follow,
+        };
+    }
+}
+
+fn update_camera(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    arena: Res<Arena>,
+    main_player: Query<&Transform, (With<MainPlayer>, Without<Camera>)>,
+    players: Query<&Transform, (With<Player>, Without<Camera>)>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    let bounds = arena.0.bounds;
+    let arena_size = Vec2::new(bounds.width, bounds.height);
+
+    let (target, view_size) = match settings.mode {
+        CameraMode::Follow => {
+            let Ok(player) = main_player.get_single() else {
+                return;
+            };
+            (
+                player.translation.truncate(),
+                Vec2::new(0.0, settings.follow_height.min(bounds.height)),
+            )
+        }
+        CameraMode::ShowEveryone => {
+            let mut min = Vec2::splat(f32::MAX);
+            let mut max = Vec2::splat(f32::MIN);
+            for player in players.iter() {
+                min = min.min(player.translation.truncate());
+                max = max.max(player.translation.truncate());
+            }
+            if min.x > max.x {
+                return;
+            }
+            let size = (max - min + Vec2::splat(settings.fit_padding * 2.0)).min(arena_size);
+            ((min + max) / 2.0, size)
+        }
+    };
+
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        projection.scaling_mode = match settings.mode {
+            CameraMode::Follow => ScalingMode::FixedVertical(view_size.y),
+            CameraMode::ShowEveryone => ScalingMode::AutoMin {
+                min_width: view_size.x,
+                min_height: view_size.y,
+            },
+        };
+
+        let center = transform.translation.truncate();
+        // The target wrapped around an edge, jump instead of sweeping across the arena
+        let wrapped = (target - center).abs().cmpgt(arena_size / 2.0).any();
+        let mut goal = center;
+        if settings.mode == CameraMode::Follow {
+            // Only move once the player leaves the deadzone
+            let offset = target - center;
+            let half_deadzone = settings.deadzone / 2.0;
+            goal += offset - offset.clamp(-half_deadzone, half_deadzone);
+        } else {
+            goal = target;
+        }
+
+        // Keep the view inside the arena, centered when the arena is smaller than the view
+        let half_view = projection.area.size() / 2.0;
+        let max_offset = (arena_size / 2.0 - half_view).max(Vec2::ZERO);
+        goal = goal.clamp(-max_offset, max_offset);
+
+        let new_center = if wrapped {
+            goal
+        } else {
+            let t = 1.0 - (-settings.lerp_speed * time.delta_seconds()).exp();
+            center.lerp(goal, t)
+        };
+        transform.translation = new_center.extend(transform.translation.z);
+    }
+}
+
+/// Puts the camera back at the origin with the default projection
+pub fn reset_camera(
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        transform.translation = Vec3::new(0.0, 0.0, transform.translation.z);
+        projection.scale = 1.0;
+        projection.scaling_mode = ScalingMode::WindowSize(1.0);
+    }
 }
@@ -0,0 +1,297 @@
+use crate::input::TextFocus;
+use crate::menu::MenuState;
+use crate::net::TcpSocketSender;
+use crate::{cleanup_entities, GameState};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use it_core::{ChatEvent, ChatMessageEvent, ClientEvent, MAX_CHAT_LENGTH};
+
+/// Messages kept in the log
+const LOG_SIZE: usize = 50;
+const LOBBY_LINES: usize = 10;
+const GAME_LINES: usize = 6;
+/// Seconds a message stays on screen in game while not typing
+const GAME_MESSAGE_LIFETIME: f64 = 10.0;
+const FONT_SIZE: f32 = 18.0;
+const NICKNAME_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+const NOTICE_COLOR: Color = Color::srgb(0.6, 0.6, 0.6);
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatLog>()
+            .init_resource::<ChatInput>()
+            .observe(on_chat_received)
+            .add_systems(
+                OnEnter(MenuState::Lobby),
+                (setup_chat_box(OnLobbyChat), start_typing),
+            )
+            .add_systems(OnEnter(GameState::Game), setup_chat_box(OnGameChat))
+            .add_systems(
+                OnExit(MenuState::Lobby),
+                (cleanup_entities::<OnLobbyChat>, stop_typing),
+            )
+            .add_systems(
+                OnExit(GameState::Game),
+                (cleanup_entities::<OnGameChat>, stop_typing),
+            )
+            .add_systems(OnEnter(MenuState::Main), clear_log)
+            .add_systems(Update, (type_message, update_chat_box).chain());
+    }
+}
+
+/// Triggered for every chat message the server relays
+#[derive(Event)]
+pub struct ChatReceivedEvent(pub ChatEvent);
+
+#[derive(Resource, Default)]
+struct ChatLog {
+    /// Messages with the time they were received at
+    messages: Vec<(ChatEvent, f64)>,
+}
+
+#[derive(Resource, Default)]
+struct ChatInput {
+    text: String,
+    typing: bool,
+}
+
+#[derive(Component, Clone, Copy)]
+struct OnLobbyChat;
+
+#[derive(Component, Clone, Copy)]
+struct OnGameChat;
+
+#[derive(Component)]
+struct ChatLogText;
+
+#[derive(Component)]
+struct ChatInputText;
+
+fn on_chat_received(
+    trigger: Trigger<ChatReceivedEvent>,
+    time: Res<Time>,
+    mut log: ResMut<ChatLog>,
+) {
+    log.messages
+        .push((trigger.event().0.clone(), time.elapsed_seconds_f64()));
+    if log.messages.len() > LOG_SIZE {
+        let overflow = log.messages.len() - LOG_SIZE;
+        log.messages.drain(..overflow);
+    }
+}
+
+fn clear_log(mut log: ResMut<ChatLog>) {
+    log.messages.clear();
+}
+
+fn start_typing(mut input: ResMut<ChatInput>, mut focus: ResMut<TextFocus>) {
+    input.typing = true;
+    focus.0 = true;
+}
+
+fn stop_typing(mut input: ResMut<ChatInput>, mut focus: ResMut<TextFocus>) {
+    input.typing = false;
+    input.text.clear();
+    focus.0 = false;
+}
+
+/// Enter opens the chat in game, then edits the message being typed.
+/// Enter sends it and Escape cancels
+fn type_message(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut input: ResMut<ChatInput>,
+    mut focus: ResMut<TextFocus>,
+    game_state: Res<State<GameState>>,
+    menu_state: Res<State<MenuState>>,
+    socket_sender: Res<TcpSocketSender>,
+) {
+    if !input.typing {
+        keyboard_events.clear();
+        let can_open =
+            *game_state.get() == GameState::Game && *menu_state.get() == MenuState::Disabled;
+        if can_open && keyboard_input.just_pressed(KeyCode::Enter) {
+            input.typing = true;
+            focus.0 = true;
+        }
+        return;
+    }
+    // The lobby chat box always has focus
+    let in_lobby = *menu_state.get() == MenuState::Lobby;
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                let message = std::mem::take(&mut input.text);
+                if !message.trim().is_empty() {
+                    let socket_sender = socket_sender.0.clone();
+                    IoTaskPool::get()
+                        .spawn(async move {
+                            let _ = socket_sender
+                                .send(ClientEvent::Chat(ChatMessageEvent { message }))
+                                .await;
+                        })
+                        .detach();
+                }
+                if !in_lobby {
+                    input.typing = false;
+                }
+            }
+            Key::Escape => {
+                input.text.clear();
+                if !in_lobby {
+                    input.typing = false;
+                }
+            }
+            Key::Backspace => {
+                input.text.pop();
+            }
+            Key::Space => push_text(&mut input.text, " "),
+            Key::Character(text) => push_text(&mut input.text, text),
+            _ => {}
+        }
+    }
+    focus.set_if_neq(TextFocus(input.typing));
+}
+
+fn push_text(text: &mut String, added: &str) {
+    for c in added.chars().filter(|c| !c.is_control()) {
+        if text.chars().count() >= MAX_CHAT_LENGTH {
+            return;
+        }
+        text.push(c);
+    }
+}
+
+fn setup_chat_box<T: Component + Copy>(marker: T) -> impl Fn(Commands, Res<State<GameState>>) {
+    move |mut commands: Commands, game_state: Res<State<GameState>>| {
+        let style = if *game_state.get() == GameState::Game {
+            Style {
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                width: Val::Px(400.0),
+                ..default()
+            }
+        } else {
+            Style {
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                bottom: Val::Px(20.0),
+                left: Val::Percent(50.0),
+                margin: UiRect::left(Val::Px(-250.0)),
+                width: Val::Px(500.0),
+                min_height: Val::Px(220.0),
+                justify_content: JustifyContent::End,
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            }
+        };
+        commands
+            .spawn((
+                NodeBundle {
+                    style,
+                    background_color: Color::BLACK.with_alpha(0.5).into(),
+                    z_index: ZIndex::Global(5),
+                    ..default()
+                },
+                marker,
+            ))
+            .with_children(|parent| {
+                parent.spawn((TextBundle::default(), ChatLogText));
+                parent.spawn((
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: FONT_SIZE,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                    ChatInputText,
+                ));
+            });
+    }
+}
+
+fn update_chat_box(
+    log: Res<ChatLog>,
+    input: Res<ChatInput>,
+    time: Res<Time>,
+    game_state: Res<State<GameState>>,
+    mut log_query: Query<(Ref<ChatLogText>, &mut Text), Without<ChatInputText>>,
+    mut input_query: Query<(&mut Text, &mut Visibility), With<ChatInputText>>,
+    mut shown_count: Local<usize>,
+) {
+    let in_game = *game_state.get() == GameState::Game;
+    let now = time.elapsed_seconds_f64();
+    let lines = if in_game { GAME_LINES } else { LOBBY_LINES };
+    let shown = log
+        .messages
+        .iter()
+        .rev()
+        .take(lines)
+        .filter(|(_, received_at)| {
+            !in_game || input.typing || now - received_at < GAME_MESSAGE_LIFETIME
+        })
+        .collect::<Vec<_>>();
+
+    // In game messages also disappear over time
+    if !log.is_changed()
+        && !input.is_changed()
+        && !log_query.iter().any(|(marker, _)| marker.is_added())
+        && shown.len() == *shown_count
+    {
+        return;
+    }
+    *shown_count = shown.len();
+    for (_, mut text) in log_query.iter_mut() {
+        let sections = shown
+            .iter()
+            .rev()
+            .flat_map(|(chat, _)| {
+                let nickname_color = if chat.client_id.is_some() {
+                    NICKNAME_COLOR
+                } else {
+                    NOTICE_COLOR
+                };
+                [
+                    TextSection::new(
+                        format!("{}: ", chat.nickname),
+                        TextStyle {
+                            font_size: FONT_SIZE,
+                            color: nickname_color,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        format!("{}\n", chat.message),
+                        TextStyle {
+                            font_size: FONT_SIZE,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                ]
+            })
+            .collect::<Vec<_>>();
+        text.sections = sections;
+    }
+    for (mut text, mut visibility) in input_query.iter_mut() {
+        let value = format!("> {}_", input.text);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+        visibility.set_if_neq(if input.typing {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
@@ -0,0 +1,152 @@
+//! Debug tooling only built with the `dev` feature: the world inspector,
+//! physics wireframes and a panel showing the state of our own systems.
+//! Everything starts hidden, F1 toggles the inspector, F2 the wireframes and
+//! F4 the panel.
+
+use crate::game::Round;
+use crate::menu::MenuState;
+use crate::net::{ConnectionState, Reconnect, ServerAddr};
+use crate::net_sim::NetSimConfig;
+use crate::player::{MainPlayer, Player};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy_rapier2d::prelude::*;
+
+pub struct DevToolsPlugin;
+
+impl Plugin for DevToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InspectorVisible>()
+            .add_plugins((
+                WorldInspectorPlugin::new().run_if(|visible: Res<InspectorVisible>| visible.0),
+                RapierDebugRenderPlugin {
+                    enabled: false,
+                    ..default()
+                },
+            ))
+            .add_systems(Startup, setup_panel)
+            .add_systems(
+                Update,
+                (toggle_dev_tools, update_panel.run_if(panel_visible)).chain(),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+struct InspectorVisible(bool);
+
+#[derive(Component)]
+struct DevPanel;
+
+fn setup_panel(mut commands: Commands) {
+    commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 14.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            })
+            .with_background_color(Color::BLACK.with_alpha(0.6)),
+            DevPanel,
+        ))
+        .insert((Visibility::Hidden, ZIndex::Global(20)));
+}
+
+fn panel_visible(query: Query<&Visibility, With<DevPanel>>) -> bool {
+    query
+        .iter()
+        .any(|visibility| visibility == Visibility::Visible)
+}
+
+fn toggle_dev_tools(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut inspector: ResMut<InspectorVisible>,
+    mut debug_render: ResMut<DebugRenderContext>,
+    mut panel: Query<&mut Visibility, With<DevPanel>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        inspector.0 = !inspector.0;
+    }
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        debug_render.enabled = !debug_render.enabled;
+    }
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        for mut visibility in panel.iter_mut() {
+            *visibility = match *visibility {
+                Visibility::Visible => Visibility::Hidden,
+                _ => Visibility::Visible,
+            };
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_panel(
+    game_state: Res<State<GameState>>,
+    menu_state: Res<State<MenuState>>,
+    connection_state: Res<ConnectionState>,
+    reconnect: Res<Reconnect>,
+    server_addr: Res<ServerAddr>,
+    network_simulation: Res<NetSimConfig>,
+    round: Res<Round>,
+    entities: Query<Entity>,
+    players: Query<(&Player, &Transform, Option<&Velocity>, Has<MainPlayer>)>,
+    mut panel: Query<&mut Text, With<DevPanel>>,
+) {
+    let simulation = if network_simulation.is_enabled() {
+        format!("{:?}", *network_simulation)
+    } else {
+        "off".to_string()
+    };
+    let mut content = vec![
+        format!("Game: {:?}  menu: {:?}", game_state.get(), menu_state.get()),
+        format!(
+            "Connection: {:?}  reconnect attempt: {}",
+            *connection_state, reconnect.attempt
+        ),
+        format!("Server: TCP {}  UDP {}", server_addr.tcp, server_addr.udp),
+        format!("Simulation: {}", simulation),
+        format!(
+            "Entities: {}  players: {}",
+            entities.iter().count(),
+            players.iter().count()
+        ),
+        format!(
+            "Round: it {}  {:.0}s left",
+            round.it.as_deref().unwrap_or("-"),
+            round.remaining_secs()
+        ),
+    ];
+    for (player, transform, velocity, main_player) in players.iter() {
+        let velocity = velocity.map_or(Vec2::ZERO, |velocity| velocity.linvel);
+        content.push(format!(
+            "{}{}{} ({:.0}, {:.0}) moving ({:.0}, {:.0})",
+            player.nickname,
+            if main_player { " (you)" } else { "" },
+            if round.it.as_ref() == Some(&player.id) {
+                " [it]"
+            } else {
+                ""
+            },
+            transform.translation.x,
+            transform.translation.y,
+            velocity.x,
+            velocity.y
+        ));
+    }
+    let content = content.join("\n");
+    for mut text in panel.iter_mut() {
+        text.sections[0].value.clone_from(&content);
+    }
+}
@@ -0,0 +1,421 @@
+use crate::camera::reset_camera;
+use crate::game::map::{MapElement, SpawnMapEvent};
+use crate::{cleanup_entities, GameState};
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::window::PrimaryWindow;
+use it_core::map::Wall;
+use it_core::{Map, Position};
+use std::path::{Path, PathBuf};
+
+/// Stem of the files new maps get, numbered when taken such as `custom-2.json`
+const NEW_MAP_FILE_STEM: &str = "custom";
+/// Size of the cells walls and spawn points snap to
+const GRID_SIZE: f32 = 32.0;
+const PAN_SPEED: f32 = 600.0;
+const ZOOM_STEP: f32 = 0.1;
+const SPAWN_POINT_COLOR: Color = Color::srgb(0.3, 0.9, 0.4);
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditorMap>()
+            .init_resource::<MapsDir>()
+            .add_systems(OnEnter(GameState::Editor), setup_editor)
+            .add_systems(
+                Update,
+                (
+                    editor_shortcuts,
+                    edit_map,
+                    pan_and_zoom,
+                    draw_grid,
+                    redraw_map.run_if(resource_changed::<EditorMap>),
+                    update_help_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Editor)),
+            )
+            .add_systems(
+                OnExit(GameState::Editor),
+                (
+                    cleanup_entities::<MapElement>,
+                    cleanup_entities::<OnEditorScreen>,
+                    reset_camera,
+                ),
+            );
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum EditorTool {
+    #[default]
+    Wall,
+    SpawnPoint,
+}
+
+/// Directory saved maps go to, the server's `maps` directory relative to the
+/// working directory like the server's default, so they join its rotation
+#[derive(Resource, Debug, Clone)]
+pub struct MapsDir(pub PathBuf);
+
+impl Default for MapsDir {
+    fn default() -> Self {
+        Self(PathBuf::from("maps"))
+    }
+}
+
+impl MapsDir {
+    /// Overrides the directory from `--maps <dir>`, the same flag as the server's
+    pub fn apply_args(&mut self, args: &[String]) {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg != "--maps" {
+                continue;
+            }
+            match args.next() {
+                Some(dir) => self.0 = PathBuf::from(dir),
+                None => eprintln!("Expected a directory after --maps"),
+            }
+        }
+    }
+}
+
+/// Map being edited
+#[derive(Resource, Default)]
+struct EditorMap {
+    map: Map,
+    /// File name inside [`MapsDir`] the map saves to
+    file: String,
+    tool: EditorTool,
+    status: String,
+}
+
+#[derive(Component)]
+struct SpawnPointMarker;
+
+#[derive(Component)]
+struct OnEditorScreen;
+
+#[derive(Component)]
+struct EditorHelpText;
+
+fn setup_editor(
+    mut commands: Commands,
+    mut editor: ResMut<EditorMap>,
+    maps_dir: Res<MapsDir>,
+    mut projections: Query<&mut OrthographicProjection>,
+) {
+    if editor.file.is_empty() {
+        editor.new_map(&maps_dir.0);
+    }
+    // Make sure the map gets drawn even if it didn't change since last time
+    editor.set_changed();
+
+    for mut projection in projections.iter_mut() {
+        projection.scaling_mode = ScalingMode::AutoMin {
+            min_width: editor.map.bounds.width,
+            min_height: editor.map.bounds.height,
+        };
+    }
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        }),
+        EditorHelpText,
+        OnEditorScreen,
+    ));
+}
+
+fn update_help_text(
+    editor: Res<EditorMap>,
+    mut text_query: Query<&mut Text, With<EditorHelpText>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    let tool = match editor.tool {
+        EditorTool::Wall => "Wall",
+        EditorTool::SpawnPoint => "Spawn point",
+    };
+    let value = format!(
+        "{} ({}) - tool: {}\n\
+         [1] wall  [2] spawn point\n\
+         [LMB] place  [RMB] delete\n\
+         [WASD] pan  [wheel] zoom\n\
+         [Ctrl+S] save  [Ctrl+O] open next map  [Ctrl+N] new\n\
+         [Esc] back to menu\n\
+         {}",
+        editor.map.name, editor.file, tool, editor.status
+    );
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value.clone_from(&value);
+    }
+}
+
+fn editor_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<EditorMap>,
+    maps_dir: Res<MapsDir>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        game_state.set(GameState::Menu);
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit1) {
+        editor.tool = EditorTool::Wall;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit2) {
+        editor.tool = EditorTool::SpawnPoint;
+    }
+
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyS) {
+        let path = maps_dir.0.join(&editor.file);
+        let result = std::fs::create_dir_all(&maps_dir.0)
+            .map_err(it_core::map::MapError::from)
+            .and_then(|_| editor.map.save(&path));
+        editor.status = match result {
+            Ok(()) => format!("Saved to {}", path.display()),
+            Err(e) => format!("Failed to save: {}", e),
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        editor.open_next_map(&maps_dir.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyN) {
+        editor.new_map(&maps_dir.0);
+        editor.status = "New map".to_string();
+    }
+}
+
+impl EditorMap {
+    /// Starts an empty map in a file that doesn't exist yet
+    fn new_map(&mut self, dir: &Path) {
+        let (name, file) = (1..)
+            .map(|n| match n {
+                1 => ("Custom".to_string(), format!("{}.json", NEW_MAP_FILE_STEM)),
+                n => (
+                    format!("Custom {}", n),
+                    format!("{}-{}.json", NEW_MAP_FILE_STEM, n),
+                ),
+            })
+            .find(|(_, file)| !dir.join(file).exists())
+            .unwrap();
+        self.map = Map { name, ..default() };
+        self.file = file;
+    }
+
+    /// Opens the map after the current file in the directory, skipping files
+    /// that aren't valid maps, so repeated presses cycle through all of them
+    fn open_next_map(&mut self, dir: &Path) {
+        let files = map_files(dir);
+        let start = files
+            .iter()
+            .position(|file| *file == self.file)
+            .map_or(0, |i| i + 1);
+        let next = files
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(files.len())
+            .find_map(|file| Some((file, Map::load(dir.join(file)).ok()?)));
+        match next {
+            Some((file, map)) => {
+                self.map = map;
+                self.file.clone_from(file);
+                self.status = format!("Loaded {}", dir.join(file).display());
+            }
+            None => self.status = format!("No maps to load in {}", dir.display()),
+        }
+    }
+}
+
+/// Names of the `.json` files in the directory, sorted
+fn map_files(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            Some(path.file_name()?.to_str()?.to_string())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn cursor_world_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor)
+}
+
+fn snap_to_grid(position: Vec2) -> Position {
+    let snapped = ((position / GRID_SIZE).floor() + 0.5) * GRID_SIZE;
+    Position {
+        x: snapped.x,
+        y: snapped.y,
+    }
+}
+
+fn edit_map(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<EditorMap>,
+) {
+    let place = mouse_input.just_pressed(MouseButton::Left);
+    let delete = mouse_input.just_pressed(MouseButton::Right);
+    if !place && !delete {
+        return;
+    }
+    let Some(cursor) = cursor_world_position(&windows, &cameras) else {
+        return;
+    };
+    let bounds = editor.map.bounds;
+    if cursor.x.abs() > bounds.width / 2.0 || cursor.y.abs() > bounds.height / 2.0 {
+        return;
+    }
+    let cell = snap_to_grid(cursor);
+    let tool = editor.tool;
+    // Only flag the map as changed when something was actually placed or deleted
+    let map = &mut editor.bypass_change_detection().map;
+
+    let changed = if place {
+        match tool {
+            EditorTool::Wall if !map.walls.iter().any(|w| w.position == cell) => {
+                map.walls.push(Wall {
+                    position: cell,
+                    width: GRID_SIZE,
+                    height: GRID_SIZE,
+                });
+                true
+            }
+            EditorTool::SpawnPoint if !map.spawn_points.contains(&cell) => {
+                map.spawn_points.push(cell);
+                true
+            }
+            _ => false,
+        }
+    } else {
+        let count = map.walls.len() + map.obstacles.len() + map.spawn_points.len();
+        map.walls.retain(|wall| {
+            (cursor.x - wall.position.x).abs() > wall.width / 2.0
+                || (cursor.y - wall.position.y).abs() > wall.height / 2.0
+        });
+        map.obstacles.retain(|obstacle| {
+            Vec2::new(obstacle.position.x, obstacle.position.y).distance(cursor) > obstacle.radius
+        });
+        map.spawn_points.retain(|spawn| *spawn != cell);
+        count != map.walls.len() + map.obstacles.len() + map.spawn_points.len()
+    };
+    if changed {
+        editor.status.clear();
+    }
+}
+
+fn pan_and_zoom(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut cameras: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    let mut direction = Vec2::ZERO;
+    if keyboard_input.any_pressed([KeyCode::KeyW, KeyCode::ArrowUp]) {
+        direction.y += 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyS, KeyCode::ArrowDown]) {
+        direction.y -= 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyA, KeyCode::ArrowLeft]) {
+        direction.x -= 1.0;
+    }
+    if keyboard_input.any_pressed([KeyCode::KeyD, KeyCode::ArrowRight]) {
+        direction.x += 1.0;
+    }
+    // Held for shortcuts such as Ctrl+S, which shouldn't scroll the view
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        direction = Vec2::ZERO;
+    }
+    let zoom: f32 = wheel_events.read().map(|event| event.y.signum()).sum();
+
+    for (mut transform, mut projection) in cameras.iter_mut() {
+        // Pan faster when zoomed out so it feels the same at any zoom level
+        let pan =
+            direction.normalize_or_zero() * PAN_SPEED * projection.scale * time.delta_seconds();
+        transform.translation += pan.extend(0.0);
+        projection.scale = (projection.scale * (1.0 - zoom * ZOOM_STEP)).clamp(0.2, 5.0);
+    }
+}
+
+fn draw_grid(editor: Res<EditorMap>, mut gizmos: Gizmos) {
+    let bounds = editor.map.bounds;
+    let half = Vec2::new(bounds.width, bounds.height) / 2.0;
+    let grid_color = Color::srgba(1.0, 1.0, 1.0, 0.08);
+
+    let mut x = (-half.x / GRID_SIZE).ceil() * GRID_SIZE;
+    while x <= half.x {
+        gizmos.line_2d(Vec2::new(x, -half.y), Vec2::new(x, half.y), grid_color);
+        x += GRID_SIZE;
+    }
+    let mut y = (-half.y / GRID_SIZE).ceil() * GRID_SIZE;
+    while y <= half.y {
+        gizmos.line_2d(Vec2::new(-half.x, y), Vec2::new(half.x, y), grid_color);
+        y += GRID_SIZE;
+    }
+    gizmos.rect_2d(Vec2::ZERO, 0.0, half * 2.0, Color::WHITE);
+}
+
+/// Rebuilds the map entities, reusing the in-game map rendering and colliders
+fn redraw_map(
+    editor: Res<EditorMap>,
+    map_elements: Query<Entity, With<MapElement>>,
+    mut commands: Commands,
+) {
+    for entity in map_elements.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.trigger(SpawnMapEvent {
+        map: editor.map.clone(),
+    });
+    for spawn_point in &editor.map.spawn_points {
+        commands.spawn((
+            Name::new("Spawn point"),
+            SpriteBundle {
+                sprite: Sprite {
+                    color: SPAWN_POINT_COLOR,
+                    custom_size: Some(Vec2::splat(GRID_SIZE / 2.0)),
+                    ..default()
+                },
+                transform: Transform::from_xyz(spawn_point.x, spawn_point.y, 0.0),
+                ..default()
+            },
+            SpawnPointMarker,
+            MapElement,
+        ));
+    }
+}
@@ -0,0 +1,238 @@
+use crate::player::{MainPlayer, Player, PlayerLabel};
+use crate::{cleanup_entities, GameState};
+use bevy::prelude::*;
+use it_core::{ClientId, TAG_COOLDOWN};
+use std::f32::consts::{FRAC_PI_4, TAU};
+
+use super::Round;
+
+const IT_COLOR: Color = Color::srgb(1.0, 0.35, 0.35);
+/// Time between two toggles of the invulnerability blink
+const BLINK_INTERVAL: f32 = 0.1;
+const PARTICLE_COUNT: usize = 16;
+const PARTICLE_LIFETIME: f32 = 0.6;
+const FLASH_DURATION: f32 = 0.3;
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.observe(on_player_tagged)
+            .add_systems(
+                Update,
+                (
+                    sync_it_marker,
+                    update_player_tint,
+                    update_particles,
+                    update_screen_flash,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(
+                OnExit(GameState::Game),
+                (
+                    cleanup_entities::<Particle>,
+                    cleanup_entities::<ScreenFlash>,
+                ),
+            );
+    }
+}
+
+/// Triggered when the server reports that a player got tagged
+#[derive(Event)]
+pub struct PlayerTaggedEvent {
+    pub it: ClientId,
+    pub tagged_by: ClientId,
+}
+
+/// Marks the player who is currently 'it'
+#[derive(Component)]
+pub struct It;
+
+#[derive(Component)]
+struct ItIcon;
+
+/// Newly tagged player who can't tag back yet, rendered blinking
+#[derive(Component)]
+pub struct Invulnerable(Timer);
+
+#[derive(Component)]
+struct Particle {
+    velocity: Vec2,
+    lifetime: Timer,
+}
+
+#[derive(Component)]
+struct ScreenFlash {
+    timer: Timer,
+    alpha: f32,
+}
+
+fn sync_it_marker(
+    round: Res<Round>,
+    players: Query<(Entity, &Player, Has<It>)>,
+    icons: Query<(Entity, &Parent), With<ItIcon>>,
+    mut commands: Commands,
+) {
+    for (entity, player, is_it) in players.iter() {
+        let should_be_it = round.it.as_ref() == Some(&player.id);
+        if should_be_it && !is_it {
+            commands.entity(entity).insert(It).with_children(|p| {
+                p.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color: IT_COLOR,
+                            custom_size: Some(Vec2::splat(6.0)),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(0.0, 26.0, 1.0)
+                            .with_rotation(Quat::from_rotation_z(FRAC_PI_4)),
+                        ..default()
+                    },
+                    ItIcon,
+                ));
+            });
+        } else if !should_be_it && is_it {
+            commands.entity(entity).remove::<It>();
+            for (icon, parent) in icons.iter() {
+                if parent.get() == entity {
+                    commands.entity(icon).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+/// Player data [`update_player_tint`] colors the sprite from
+type TintedPlayer = (
+    Entity,
+    &'static mut Sprite,
+    Has<It>,
+    Option<&'static mut Invulnerable>,
+);
+
+fn update_player_tint(
+    time: Res<Time>,
+    mut players: Query<TintedPlayer, With<Player>>,
+    its: Query<(), With<It>>,
+    mut labels: Query<(&Parent, &mut Text), With<PlayerLabel>>,
+    mut commands: Commands,
+) {
+    for (entity, mut sprite, is_it, invulnerable) in players.iter_mut() {
+        let mut color = if is_it { IT_COLOR } else { Color::WHITE };
+        if let Some(mut invulnerable) = invulnerable {
+            invulnerable.0.tick(time.delta());
+            if invulnerable.0.finished() {
+                commands.entity(entity).remove::<Invulnerable>();
+            } else if ((invulnerable.0.elapsed_secs() / BLINK_INTERVAL) as u32).is_multiple_of(2) {
+                color = color.with_alpha(0.2);
+            }
+        }
+        sprite.color = color;
+    }
+    for (parent, mut text) in labels.iter_mut() {
+        let color = if its.contains(parent.get()) {
+            IT_COLOR
+        } else {
+            Color::WHITE
+        };
+        text.sections[0].style.color = color;
+    }
+}
+
+fn on_player_tagged(
+    trigger: Trigger<PlayerTaggedEvent>,
+    players: Query<(Entity, &Player, &Transform, Has<MainPlayer>)>,
+    mut commands: Commands,
+) {
+    let event = trigger.event();
+    let mut involves_main_player = false;
+    for (entity, player, transform, is_main) in players.iter() {
+        if player.id == event.tagged_by && is_main {
+            involves_main_player = true;
+        }
+        if player.id != event.it {
+            continue;
+        }
+        involves_main_player |= is_main;
+
+        commands
+            .entity(entity)
+            .insert(Invulnerable(Timer::new(TAG_COOLDOWN, TimerMode::Once)));
+
+        for i in 0..PARTICLE_COUNT {
+            let angle = TAU * i as f32 / PARTICLE_COUNT as f32;
+            let speed = if i % 2 == 0 { 120.0 } else { 70.0 };
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: IT_COLOR,
+                        custom_size: Some(Vec2::splat(3.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(
+                        transform.translation.truncate().extend(2.0),
+                    ),
+                    ..default()
+                },
+                Particle {
+                    velocity: Vec2::from_angle(angle) * speed,
+                    lifetime: Timer::from_seconds(PARTICLE_LIFETIME, TimerMode::Once),
+                },
+            ));
+        }
+    }
+
+    let alpha = if involves_main_player { 0.5 } else { 0.15 };
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::WHITE.with_alpha(alpha).into(),
+            z_index: ZIndex::Global(100),
+            ..default()
+        },
+        ScreenFlash {
+            timer: Timer::from_seconds(FLASH_DURATION, TimerMode::Once),
+            alpha,
+        },
+    ));
+}
+
+fn update_particles(
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+    mut commands: Commands,
+) {
+    for (entity, mut particle, mut transform, mut sprite) in particles.iter_mut() {
+        particle.lifetime.tick(time.delta());
+        if particle.lifetime.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+        sprite.color.set_alpha(1.0 - particle.lifetime.fraction());
+    }
+}
+
+fn update_screen_flash(
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut ScreenFlash, &mut BackgroundColor)>,
+    mut commands: Commands,
+) {
+    for (entity, mut flash, mut background) in flashes.iter_mut() {
+        flash.timer.tick(time.delta());
+        if flash.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        background
+            .0
+            .set_alpha(flash.alpha * (1.0 - flash.timer.fraction()));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::input::{Action, ActionState};
+use crate::net::UdpSocketSender;
+use crate::player::{MainPlayer, Player, PlayerLabel};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use it_core::{ClientEvent, ClientId, Emote, EmoteEvent};
+
+/// Seconds a speech bubble stays above a player
+const BUBBLE_DURATION: f32 = 2.5;
+const BUBBLE_FONT_SIZE: f32 = 10.0;
+const BUBBLE_PADDING: Vec2 = Vec2::new(6.0, 4.0);
+
+pub struct EmotesPlugin;
+
+impl Plugin for EmotesPlugin {
+    fn build(&self, app: &mut App) {
+        app.observe(show_emote).add_systems(
+            Update,
+            (send_emotes, update_bubbles).run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+/// Triggered when the server relays an emote, including the main player's own
+#[derive(Event)]
+pub struct EmoteReceivedEvent {
+    pub client_id: ClientId,
+    pub emote: Emote,
+}
+
+#[derive(Component)]
+struct EmoteBubble(Timer);
+
+/// The server drops emotes sent during the cooldown
+fn send_emotes(
+    actions: Res<ActionState>,
+    socket_sender: Res<UdpSocketSender>,
+    player_query: Query<&Player, With<MainPlayer>>,
+) {
+    let Some(emote) = Action::ALL
+        .into_iter()
+        .filter(|action| actions.just_pressed(*action))
+        .find_map(Action::emote)
+    else {
+        return;
+    };
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let socket_sender = socket_sender.0.clone();
+    let client_id = player.id.clone();
+    IoTaskPool::get()
+        .spawn(async move {
+            let _ = socket_sender
+                .send(ClientEvent::Emote(EmoteEvent { client_id, emote }))
+                .await;
+        })
+        .detach();
+}
+
+fn show_emote(
+    trigger: Trigger<EmoteReceivedEvent>,
+    players: Query<(&Player, &Children)>,
+    labels: Query<Option<&Children>, With<PlayerLabel>>,
+    bubbles: Query<Entity, With<EmoteBubble>>,
+    mut commands: Commands,
+) {
+    let event = trigger.event();
+    let Some((_, children)) = players
+        .iter()
+        .find(|(player, _)| player.id == event.client_id)
+    else {
+        return;
+    };
+    let Some((label, label_children)) = children
+        .iter()
+        .find_map(|child| labels.get(*child).ok().map(|c| (*child, c)))
+    else {
+        return;
+    };
+    // A new emote replaces the one still showing
+    for child in label_children.into_iter().flatten() {
+        if bubbles.contains(*child) {
+            commands.entity(*child).despawn_recursive();
+        }
+    }
+
+    let text = event.emote.text();
+    let size = Vec2::new(text.len() as f32 * BUBBLE_FONT_SIZE * 0.6, BUBBLE_FONT_SIZE)
+        + BUBBLE_PADDING * 2.0;
+    commands.entity(label).with_children(|parent| {
+        parent
+            .spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::WHITE,
+                        custom_size: Some(size),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 16.0, 1.0),
+                    ..default()
+                },
+                EmoteBubble(Timer::from_seconds(BUBBLE_DURATION, TimerMode::Once)),
+            ))
+            .with_children(|parent| {
+                parent.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        text,
+                        TextStyle {
+                            font_size: BUBBLE_FONT_SIZE,
+                            color: Color::BLACK,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                    ..default()
+                });
+            });
+    });
+}
+
+fn update_bubbles(
+    time: Res<Time>,
+    mut bubbles: Query<(Entity, &mut EmoteBubble, &mut Sprite)>,
+    mut commands: Commands,
+) {
+    for (entity, mut bubble, mut sprite) in bubbles.iter_mut() {
+        bubble.0.tick(time.delta());
+        if bubble.0.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        // Fade out during the last half second
+        sprite
+            .color
+            .set_alpha((bubble.0.remaining_secs() / 0.5).min(1.0));
+    }
+}
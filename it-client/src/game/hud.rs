@@ -0,0 +1,203 @@
+use crate::input::{Action, ActionState};
+use crate::player::{MainPlayer, Player};
+use crate::{cleanup_entities, GameState};
+use bevy::prelude::*;
+use it_core::ClientId;
+
+use super::{Round, Scoreboard};
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), setup_hud)
+            .add_systems(
+                Update,
+                (
+                    update_it_text,
+                    update_round_timer,
+                    update_scoreboard,
+                    toggle_scoreboard,
+                )
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), cleanup_entities::<OnGameHud>);
+    }
+}
+
+#[derive(Component)]
+struct OnGameHud;
+
+#[derive(Component)]
+struct ItText;
+
+#[derive(Component)]
+struct RoundTimerText;
+
+#[derive(Component)]
+struct ScoreboardPanel;
+
+#[derive(Component)]
+struct ScoreboardRows;
+
+fn hud_text(value: impl Into<String>, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            color: Color::WHITE,
+            ..default()
+        },
+    )
+}
+
+fn setup_hud(mut commands: Commands) {
+    // Root node
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            OnGameHud,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                hud_text("", 30.0).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    ..default()
+                }),
+                ItText,
+            ));
+            parent.spawn((
+                hud_text("", 30.0).with_style(Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    right: Val::Px(20.0),
+                    ..default()
+                }),
+                RoundTimerText,
+            ));
+            parent
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Stretch,
+                            padding: UiRect::all(Val::Px(20.0)),
+                            row_gap: Val::Px(8.0),
+                            min_width: Val::Px(400.0),
+                            ..default()
+                        },
+                        background_color: Color::srgba(0.0, 0.0, 0.0, 0.75).into(),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    ScoreboardPanel,
+                ))
+                .with_children(|parent| {
+                    parent.spawn(hud_text("Scoreboard", 30.0));
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                row_gap: Val::Px(4.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        ScoreboardRows,
+                    ));
+                });
+        });
+}
+
+fn update_it_text(
+    round: Res<Round>,
+    players: Query<(&Player, Has<MainPlayer>)>,
+    mut text_query: Query<&mut Text, With<ItText>>,
+) {
+    let value = match &round.it {
+        Some(it) => match players.iter().find(|(player, _)| player.id == *it) {
+            Some((_, true)) => "You are it!".to_string(),
+            Some((player, false)) => format!("{} is it", player.nickname),
+            None => String::new(),
+        },
+        None => "Round over".to_string(),
+    };
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value.clone_from(&value);
+        }
+    }
+}
+
+fn update_round_timer(round: Res<Round>, mut text_query: Query<&mut Text, With<RoundTimerText>>) {
+    let remaining = round.remaining_secs().ceil() as u32;
+    let value = format!("{}:{:02}", remaining / 60, remaining % 60);
+    for mut text in text_query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value.clone_from(&value);
+        }
+    }
+}
+
+fn update_scoreboard(
+    scoreboard: Res<Scoreboard>,
+    round: Res<Round>,
+    rows_query: Query<Entity, With<ScoreboardRows>>,
+    mut shown_it: Local<Option<ClientId>>,
+    mut commands: Commands,
+) {
+    // The 'it' row is highlighted, other round updates don't show here
+    if !scoreboard.is_changed() && *shown_it == round.it {
+        return;
+    }
+    shown_it.clone_from(&round.it);
+    for rows in rows_query.iter() {
+        commands
+            .entity(rows)
+            .despawn_descendants()
+            .with_children(|parent| {
+                parent.spawn(hud_text(
+                    format!("{:<20}{:>10}{:>10}", "Player", "It", "Ping"),
+                    20.0,
+                ));
+                for player in &scoreboard.players {
+                    let ping = player
+                        .ping_ms
+                        .map_or("-".to_string(), |ping| format!("{}ms", ping));
+                    let mut row = hud_text(
+                        format!("{:<20}{:>10}{:>10}", player.nickname, player.it_count, ping),
+                        20.0,
+                    );
+                    if round.it.as_ref() == Some(&player.client_id) {
+                        row.text.sections[0].style.color = Color::srgb(1.0, 0.3, 0.3);
+                    }
+                    parent.spawn(row);
+                }
+            });
+    }
+}
+
+fn toggle_scoreboard(
+    actions: Res<ActionState>,
+    mut panel_query: Query<&mut Visibility, With<ScoreboardPanel>>,
+) {
+    let visibility = if actions.pressed(Action::Scoreboard) {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+    for mut panel in panel_query.iter_mut() {
+        panel.set_if_neq(visibility);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{cleanup_entities, GameState};
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use bevy_rapier2d::prelude::*;
+use it_core::Map;
+
+pub const WALL_COLOR: Color = Color::srgb(0.35, 0.35, 0.4);
+pub const OBSTACLE_COLOR: Color = Color::srgb(0.3, 0.45, 0.3);
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MapBodiesPlugin)
+            .observe(add_wall_sprite)
+            .observe(add_obstacle_mesh);
+    }
+}
+
+/// Walls and obstacles as physics bodies, [`MapPlugin`] adds what renders them
+pub struct MapBodiesPlugin;
+
+impl Plugin for MapBodiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.observe(spawn_map)
+            .add_systems(OnExit(GameState::Game), cleanup_entities::<MapElement>);
+    }
+}
+
+#[derive(Event)]
+pub struct SpawnMapEvent {
+    pub map: Map,
+}
+
+/// Entity rendering part of the map
+#[derive(Component)]
+pub struct MapElement;
+
+#[derive(Component)]
+pub struct Wall {
+    pub size: Vec2,
+}
+
+#[derive(Component)]
+pub struct Obstacle {
+    pub radius: f32,
+}
+
+fn spawn_map(trigger: Trigger<SpawnMapEvent>, mut commands: Commands) {
+    let map = &trigger.event().map;
+    info!("Spawning map {}", map.name);
+
+    for wall in &map.walls {
+        commands.spawn((
+            Name::new("Wall"),
+            Wall {
+                size: Vec2::new(wall.width, wall.height),
+            },
+            TransformBundle::from_transform(Transform::from_xyz(
+                wall.position.x,
+                wall.position.y,
+                -1.0,
+            )),
+            RigidBody::Fixed,
+            Collider::cuboid(wall.width / 2.0, wall.height / 2.0),
+            MapElement,
+        ));
+    }
+
+    for obstacle in &map.obstacles {
+        commands.spawn((
+            Name::new("Obstacle"),
+            Obstacle {
+                radius: obstacle.radius,
+            },
+            TransformBundle::from_transform(Transform::from_xyz(
+                obstacle.position.x,
+                obstacle.position.y,
+                -1.0,
+            )),
+            RigidBody::Fixed,
+            Collider::ball(obstacle.radius),
+            MapElement,
+        ));
+    }
+}
+
+fn add_wall_sprite(trigger: Trigger<OnAdd, Wall>, walls: Query<&Wall>, mut commands: Commands) {
+    let Ok(wall) = walls.get(trigger.entity()) else {
+        return;
+    };
+    commands.entity(trigger.entity()).insert((
+        Sprite {
+            color: WALL_COLOR,
+            custom_size: Some(wall.size),
+            ..default()
+        },
+        Handle::<Image>::default(),
+        VisibilityBundle::default(),
+    ));
+}
+
+fn add_obstacle_mesh(
+    trigger: Trigger<OnAdd, Obstacle>,
+    obstacles: Query<&Obstacle>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut obstacle_material: Local<Option<Handle<ColorMaterial>>>,
+) {
+    let Ok(obstacle) = obstacles.get(trigger.entity()) else {
+        return;
+    };
+    let material = obstacle_material
+        .get_or_insert_with(|| materials.add(OBSTACLE_COLOR))
+        .clone();
+    commands.entity(trigger.entity()).insert((
+        Mesh2dHandle(meshes.add(Circle::new(obstacle.radius))),
+        material,
+        VisibilityBundle::default(),
+    ));
+}
@@ -0,0 +1,277 @@
+use crate::game::effects::It;
+use crate::game::map::{OBSTACLE_COLOR, WALL_COLOR};
+use crate::input::{Action, ActionState};
+use crate::player::{MainPlayer, Player};
+use crate::settings::Settings;
+use crate::{cleanup_entities, GameState};
+use bevy::prelude::*;
+use it_core::Position;
+use serde::{Deserialize, Serialize};
+
+use super::Arena;
+
+const DOT_SIZE: f32 = 6.0;
+const IT_DOT_COLOR: Color = Color::srgb(1.0, 0.25, 0.25);
+const MAIN_PLAYER_DOT_COLOR: Color = Color::srgb(0.3, 0.9, 0.4);
+const PLAYER_DOT_COLOR: Color = Color::WHITE;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MinimapSettings>()
+            .add_systems(OnEnter(GameState::Game), setup_minimap)
+            .add_systems(
+                Update,
+                apply_minimap_corner.run_if(resource_changed::<Settings>),
+            )
+            .add_systems(
+                Update,
+                (toggle_minimap, apply_minimap_settings, update_dots)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), cleanup_entities::<Minimap>);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MinimapCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl MinimapCorner {
+    pub fn next(self) -> Self {
+        match self {
+            MinimapCorner::TopLeft => MinimapCorner::TopRight,
+            MinimapCorner::TopRight => MinimapCorner::BottomRight,
+            MinimapCorner::BottomRight => MinimapCorner::BottomLeft,
+            MinimapCorner::BottomLeft => MinimapCorner::TopLeft,
+        }
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            MinimapCorner::TopLeft => "Top left",
+            MinimapCorner::TopRight => "Top right",
+            MinimapCorner::BottomLeft => "Bottom left",
+            MinimapCorner::BottomRight => "Bottom right",
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct MinimapSettings {
+    pub visible: bool,
+    pub corner: MinimapCorner,
+    /// Width of the minimap in pixels, the height follows the arena ratio
+    pub width: f32,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            corner: MinimapCorner::default(),
+            width: 200.0,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Minimap;
+
+/// Dot following a player on the minimap
+#[derive(Component)]
+struct MinimapDot(Entity);
+
+/// Position of a point of the arena on the minimap, in percent from the top left corner
+fn minimap_percent(arena: &Arena, position: &Position) -> Vec2 {
+    let bounds = arena.0.bounds;
+    Vec2::new(
+        (position.x / bounds.width + 0.5) * 100.0,
+        (0.5 - position.y / bounds.height) * 100.0,
+    )
+}
+
+fn setup_minimap(mut commands: Commands, arena: Res<Arena>) {
+    let bounds = arena.0.bounds;
+
+    commands
+        .spawn((
+            Name::new("Minimap"),
+            NodeBundle {
+                // Size and corner are set from the settings once spawned
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    border: UiRect::all(Val::Px(1.0)),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+                border_color: Color::WHITE.into(),
+                ..default()
+            },
+            Minimap,
+        ))
+        .with_children(|parent| {
+            for wall in &arena.0.walls {
+                let top_left = minimap_percent(
+                    &arena,
+                    &Position {
+                        x: wall.position.x - wall.width / 2.0,
+                        y: wall.position.y + wall.height / 2.0,
+                    },
+                );
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Percent(top_left.x),
+                        top: Val::Percent(top_left.y),
+                        width: Val::Percent(wall.width / bounds.width * 100.0),
+                        height: Val::Percent(wall.height / bounds.height * 100.0),
+                        ..default()
+                    },
+                    background_color: WALL_COLOR.into(),
+                    ..default()
+                });
+            }
+            for obstacle in &arena.0.obstacles {
+                let top_left = minimap_percent(
+                    &arena,
+                    &Position {
+                        x: obstacle.position.x - obstacle.radius,
+                        y: obstacle.position.y + obstacle.radius,
+                    },
+                );
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Percent(top_left.x),
+                        top: Val::Percent(top_left.y),
+                        width: Val::Percent(obstacle.radius * 2.0 / bounds.width * 100.0),
+                        height: Val::Percent(obstacle.radius * 2.0 / bounds.height * 100.0),
+                        ..default()
+                    },
+                    background_color: OBSTACLE_COLOR.into(),
+                    border_radius: BorderRadius::MAX,
+                    ..default()
+                });
+            }
+        });
+}
+
+/// Moves the minimap to the corner picked in the settings menu
+fn apply_minimap_corner(settings: Res<Settings>, mut minimap: ResMut<MinimapSettings>) {
+    if minimap.corner != settings.minimap_corner {
+        minimap.corner = settings.minimap_corner;
+    }
+}
+
+fn toggle_minimap(actions: Res<ActionState>, mut settings: ResMut<MinimapSettings>) {
+    if actions.just_pressed(Action::Minimap) {
+        settings.visible = !settings.visible;
+    }
+}
+
+fn apply_minimap_settings(
+    settings: Res<MinimapSettings>,
+    arena: Res<Arena>,
+    mut minimaps: Query<(Ref<Minimap>, &mut Style, &mut Visibility)>,
+) {
+    for (minimap, mut style, mut visibility) in minimaps.iter_mut() {
+        if !settings.is_changed() && !minimap.is_added() {
+            continue;
+        }
+        let margin = Val::Px(10.0);
+        let (top, bottom) = match settings.corner {
+            MinimapCorner::TopLeft | MinimapCorner::TopRight => (margin, Val::Auto),
+            MinimapCorner::BottomLeft | MinimapCorner::BottomRight => (Val::Auto, margin),
+        };
+        let (left, right) = match settings.corner {
+            MinimapCorner::TopLeft | MinimapCorner::BottomLeft => (margin, Val::Auto),
+            MinimapCorner::TopRight | MinimapCorner::BottomRight => (Val::Auto, margin),
+        };
+        style.top = top;
+        style.bottom = bottom;
+        style.left = left;
+        style.right = right;
+        style.width = Val::Px(settings.width);
+        style.height = Val::Px(settings.width * arena.0.bounds.height / arena.0.bounds.width);
+        *visibility = if settings.visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Player data a minimap dot is placed and colored from
+type DotPlayer = (Entity, &'static Transform, Has<It>, Has<MainPlayer>);
+
+fn update_dots(
+    arena: Res<Arena>,
+    players: Query<DotPlayer, With<Player>>,
+    mut dots: Query<(Entity, &MinimapDot, &mut Style, &mut BackgroundColor)>,
+    minimaps: Query<Entity, With<Minimap>>,
+    mut commands: Commands,
+) {
+    let Ok(minimap) = minimaps.get_single() else {
+        return;
+    };
+
+    for (dot_entity, dot, mut style, mut background) in dots.iter_mut() {
+        let Ok((_, transform, is_it, is_main)) = players.get(dot.0) else {
+            commands.entity(dot_entity).despawn_recursive();
+            continue;
+        };
+        let percent = minimap_percent(
+            &arena,
+            &Position {
+                x: transform.translation.x,
+                y: transform.translation.y,
+            },
+        );
+        style.left = Val::Percent(percent.x);
+        style.top = Val::Percent(percent.y);
+        background.0 = if is_it {
+            IT_DOT_COLOR
+        } else if is_main {
+            MAIN_PLAYER_DOT_COLOR
+        } else {
+            PLAYER_DOT_COLOR
+        };
+    }
+
+    for (player, ..) in players.iter() {
+        if dots.iter().any(|(_, dot, ..)| dot.0 == player) {
+            continue;
+        }
+        commands.entity(minimap).with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(DOT_SIZE),
+                        height: Val::Px(DOT_SIZE),
+                        // Center the dot on the player position
+                        margin: UiRect::new(
+                            Val::Px(-DOT_SIZE / 2.0),
+                            Val::ZERO,
+                            Val::Px(-DOT_SIZE / 2.0),
+                            Val::ZERO,
+                        ),
+                        ..default()
+                    },
+                    border_radius: BorderRadius::MAX,
+                    z_index: ZIndex::Local(1),
+                    ..default()
+                },
+                MinimapDot(player),
+            ));
+        });
+    }
+}
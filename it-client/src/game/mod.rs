@@ -0,0 +1,81 @@
+use crate::GameState;
+use bevy::prelude::*;
+use it_core::{ClientId, Map, PlayerScore};
+
+pub mod effects;
+pub mod emotes;
+pub mod hud;
+pub mod map;
+pub mod minimap;
+
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            RoundPlugin,
+            hud::HudPlugin,
+            effects::EffectsPlugin,
+            emotes::EmotesPlugin,
+            map::MapPlugin,
+            minimap::MinimapPlugin,
+        ));
+    }
+}
+
+/// State of the game as told by the server, without anything to show it
+pub struct RoundPlugin;
+
+impl Plugin for RoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Arena>()
+            .init_resource::<Round>()
+            .init_resource::<Scoreboard>()
+            .add_systems(Update, tick_round.run_if(in_state(GameState::Game)))
+            .add_systems(OnExit(GameState::Game), reset_round);
+    }
+}
+
+/// Map of the current game, as sent by the server when the game starts
+#[derive(Resource, Default)]
+pub struct Arena(pub Map);
+
+/// State of the current round as last reported by the server
+#[derive(Resource, Default)]
+pub struct Round {
+    /// Player who is 'it', `None` between rounds
+    pub it: Option<ClientId>,
+    pub timer: Option<Timer>,
+}
+
+impl Round {
+    pub fn remaining_secs(&self) -> f32 {
+        self.timer.as_ref().map_or(0.0, |t| t.remaining_secs())
+    }
+}
+
+/// Triggered when the server starts a round
+#[derive(Event)]
+pub struct RoundStartEvent;
+
+/// Triggered when the server ends the current round
+#[derive(Event)]
+pub struct RoundEndEvent;
+
+/// Latest scores sent by the server for the lobby
+#[derive(Resource, Default)]
+pub struct Scoreboard {
+    pub players: Vec<PlayerScore>,
+}
+
+fn tick_round(time: Res<Time>, mut round: ResMut<Round>) {
+    // Only the countdown moves, systems watching the round shouldn't rerun every frame
+    if let Some(timer) = round.bypass_change_detection().timer.as_mut() {
+        timer.tick(time.delta());
+    }
+}
+
+fn reset_round(mut round: ResMut<Round>, mut scoreboard: ResMut<Scoreboard>) {
+    *round = Round::default();
+    scoreboard.players.clear();
+}
@@ -0,0 +1,637 @@
+//! Client without a window for automated matches, such as in CI on a machine
+//! without a GPU. Networking, rounds, physics and movement run the same as in
+//! the game, only without sprites, and the main player is steered by a bot, or
+//! by a script given with `--script <file>`.
+//!
+//! The client exits with a summary once the script ends or after
+//! `--duration <secs>`, with an error code if the connection failed, an
+//! `expect` timed out or the bot never got into a game.
+
+use crate::chat::ChatReceivedEvent;
+use crate::game::effects::PlayerTaggedEvent;
+use crate::game::map::MapBodiesPlugin;
+use crate::game::{Round, RoundEndEvent, RoundPlugin, RoundStartEvent, Scoreboard};
+use crate::input::{Action, ActionState};
+use crate::menu::MenuState;
+use crate::net::{
+    ConnectionState, NetCounters, NetStats, NetworkPlugin, ServerAddr, TcpSocketSender, Transport,
+    UdpSocketSender,
+};
+use crate::net_sim::NetSimConfig;
+use crate::player::{MainPlayer, Player, PlayerMovementPlugin};
+use crate::GameState;
+use async_channel::Sender;
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::tasks::IoTaskPool;
+use bevy::utils::HashSet;
+use bevy_rapier2d::prelude::RapierPhysicsPlugin;
+use it_core::{ChatMessageEvent, ClientEvent, ClientId, Emote, EmoteEvent};
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::TAU;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Time between frames, the loop would spin as fast as it can otherwise
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+const DEFAULT_DURATION: Duration = Duration::from_secs(60);
+const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time the bot keeps heading the same way while wandering
+const BOT_TURN_INTERVAL: Duration = Duration::from_millis(1500);
+/// Distance under which the bot dashes at the player it chases
+const BOT_DASH_RANGE: f32 = 80.0;
+
+/// Options of `--headless`
+pub struct HeadlessConfig {
+    /// Commands played instead of the bot
+    pub script: Option<PathBuf>,
+    /// Longest time the client runs for
+    pub duration: Duration,
+    /// Sockets, or a memory network for tests
+    pub transport: Transport,
+}
+
+impl HeadlessConfig {
+    /// Reads `--script <file>` and `--duration <secs>`, `None` without `--headless`
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        if !args.iter().any(|arg| arg == "--headless") {
+            return None;
+        }
+        let mut config = Self {
+            script: None,
+            duration: DEFAULT_DURATION,
+            transport: Transport::Sockets,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--script" => config.script = args.next().map(PathBuf::from),
+                "--duration" => match args.next().map(|value| value.parse::<f32>()) {
+                    Some(Ok(secs)) if secs > 0.0 => {
+                        config.duration = Duration::from_secs_f32(secs);
+                    }
+                    _ => eprintln!("Expected a number of seconds after --duration"),
+                },
+                _ => {}
+            }
+        }
+        Some(config)
+    }
+}
+
+/// Runs the client without a window until it is done, see the module docs
+pub fn run(
+    config: HeadlessConfig,
+    network_simulation: NetSimConfig,
+    server_addr: ServerAddr,
+) -> AppExit {
+    let script = match config.script.as_deref().map(Script::load).transpose() {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("{}", e);
+            return AppExit::error();
+        }
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(FRAME_TIME)),
+        LogPlugin::default(),
+        StatesPlugin,
+        TransformPlugin,
+        HierarchyPlugin,
+        // Rapier looks up meshes for colliders built from them
+        AssetPlugin::default(),
+        RapierPhysicsPlugin::<()>::pixels_per_meter(32.0),
+    ))
+    .init_asset::<Mesh>()
+    .init_state::<GameState>()
+    .init_state::<MenuState>()
+    .init_resource::<ActionState>()
+    .add_plugins((
+        NetworkPlugin,
+        RoundPlugin,
+        PlayerMovementPlugin,
+        MapBodiesPlugin,
+        HeadlessPlugin,
+    ))
+    .insert_resource(network_simulation)
+    .insert_resource(server_addr)
+    .insert_resource(config.transport)
+    .insert_resource(TimeLimit(config.duration));
+    if let Some(script) = script {
+        app.insert_resource(script);
+    }
+    app.run()
+}
+
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MatchStats>()
+            .init_resource::<Bot>()
+            .observe(count_round_start)
+            .observe(count_round_end)
+            .observe(count_tag)
+            .observe(count_chat)
+            .observe(report)
+            // Takes the place of the keyboard, before the player systems read it
+            .add_systems(
+                PreUpdate,
+                (
+                    (bot_join, steer_bot).run_if(not(resource_exists::<Script>)),
+                    run_script.run_if(resource_exists::<Script>),
+                ),
+            )
+            .add_systems(Update, check_time_limit)
+            .add_systems(OnEnter(GameState::Game), count_game)
+            .add_systems(OnEnter(MenuState::Error), connection_failed);
+    }
+}
+
+/// Longest time the client runs for
+#[derive(Resource)]
+struct TimeLimit(Duration);
+
+/// What happened during the run, printed when exiting
+#[derive(Resource, Default)]
+struct MatchStats {
+    games: u32,
+    rounds_started: u32,
+    rounds: u32,
+    tags: u32,
+    /// Tags that made the main player 'it'
+    times_tagged: u32,
+    chat_messages: u32,
+}
+
+/// Triggered once the client is done, with the reason when it failed
+#[derive(Event)]
+struct Finish(Option<String>);
+
+fn send(sender: &Sender<ClientEvent>, event: ClientEvent) {
+    let sender = sender.clone();
+    IoTaskPool::get()
+        .spawn(async move {
+            let _ = sender.send(event).await;
+        })
+        .detach();
+}
+
+fn count_game(mut stats: ResMut<MatchStats>) {
+    stats.games += 1;
+}
+
+fn count_round_start(_trigger: Trigger<RoundStartEvent>, mut stats: ResMut<MatchStats>) {
+    stats.rounds_started += 1;
+}
+
+fn count_round_end(_trigger: Trigger<RoundEndEvent>, mut stats: ResMut<MatchStats>) {
+    stats.rounds += 1;
+}
+
+fn count_tag(
+    trigger: Trigger<PlayerTaggedEvent>,
+    main_player: Query<&Player, With<MainPlayer>>,
+    mut stats: ResMut<MatchStats>,
+) {
+    stats.tags += 1;
+    if main_player
+        .get_single()
+        .is_ok_and(|player| player.id == trigger.event().it)
+    {
+        stats.times_tagged += 1;
+    }
+}
+
+fn count_chat(_trigger: Trigger<ChatReceivedEvent>, mut stats: ResMut<MatchStats>) {
+    stats.chat_messages += 1;
+}
+
+fn connection_failed(connection_state: Res<ConnectionState>, mut commands: Commands) {
+    let reason = match &*connection_state {
+        ConnectionState::Failed(e) => format!("connection failed: {}", e),
+        _ => "lost connection to the server".to_string(),
+    };
+    commands.trigger(Finish(Some(reason)));
+}
+
+fn check_time_limit(
+    time: Res<Time<Real>>,
+    limit: Res<TimeLimit>,
+    stats: Res<MatchStats>,
+    script: Option<Res<Script>>,
+    mut commands: Commands,
+) {
+    if time.elapsed() < limit.0 {
+        return;
+    }
+    let failure = if script.is_some() {
+        Some(format!("script still running after {:?}", limit.0))
+    } else if stats.games == 0 {
+        Some(format!("no game started within {:?}", limit.0))
+    } else {
+        None
+    };
+    commands.trigger(Finish(failure));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn report(
+    trigger: Trigger<Finish>,
+    time: Res<Time<Real>>,
+    stats: Res<MatchStats>,
+    counters: Res<NetCounters>,
+    net_stats: Res<NetStats>,
+    scoreboard: Res<Scoreboard>,
+    mut finished: Local<bool>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // Several reasons to stop can come up on the same frame
+    if *finished {
+        return;
+    }
+    *finished = true;
+
+    let failure = &trigger.event().0;
+    println!(
+        "Headless client stopped after {:.1}s: {}",
+        time.elapsed_seconds(),
+        failure.as_deref().unwrap_or("ok")
+    );
+    println!(
+        "Games: {}, rounds: {}, tags: {} ({} on us), chat messages: {}",
+        stats.games, stats.rounds, stats.tags, stats.times_tagged, stats.chat_messages
+    );
+    let counters = &counters.0;
+    println!(
+        "TCP sent {} packets ({} bytes), received {} ({} bytes)",
+        counters.tcp_sent.packets(),
+        counters.tcp_sent.bytes(),
+        counters.tcp_received.packets(),
+        counters.tcp_received.bytes()
+    );
+    println!(
+        "UDP sent {} packets ({} bytes), received {} ({} bytes)",
+        counters.udp_sent.packets(),
+        counters.udp_sent.bytes(),
+        counters.udp_received.packets(),
+        counters.udp_received.bytes()
+    );
+    if let Some(rtt) = net_stats.rtt_ms {
+        println!(
+            "Round trip {:.0} ms, jitter {:.1} ms, packet loss {:.0}%",
+            rtt,
+            net_stats.jitter_ms,
+            net_stats.packet_loss().unwrap_or(0.0) * 100.0
+        );
+    }
+    for player in &scoreboard.players {
+        let ping = player
+            .ping_ms
+            .map_or_else(|| "-".to_string(), |ping| format!("{} ms", ping));
+        println!(
+            "  {:<20} it {:>3} times, ping {}",
+            player.nickname, player.it_count, ping
+        );
+    }
+
+    exit.send(match failure {
+        Some(_) => AppExit::error(),
+        None => AppExit::Success,
+    });
+}
+
+/// State of the bot steering the main player
+#[derive(Resource)]
+struct Bot {
+    turn: Timer,
+    heading: Vec2,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self {
+            turn: Timer::new(BOT_TURN_INTERVAL, TimerMode::Repeating),
+            heading: Vec2::X,
+        }
+    }
+}
+
+/// Joins a lobby whenever the bot is connected and back in the main menu
+fn bot_join(
+    connection_state: Res<ConnectionState>,
+    game_state: Res<State<GameState>>,
+    menu_state: Res<State<MenuState>>,
+    socket_sender: Res<TcpSocketSender>,
+    mut next_menu_state: ResMut<NextState<MenuState>>,
+) {
+    if *connection_state == ConnectionState::Connected
+        && *game_state.get() == GameState::Menu
+        && *menu_state.get() == MenuState::Main
+    {
+        send(&socket_sender.0, ClientEvent::Join);
+        next_menu_state.set(MenuState::Lobby);
+    }
+}
+
+/// Chases the closest player while 'it', runs away from 'it' otherwise
+fn steer_bot(
+    time: Res<Time>,
+    round: Res<Round>,
+    mut bot: ResMut<Bot>,
+    mut actions: ResMut<ActionState>,
+    main_player: Query<(&Transform, &Player), With<MainPlayer>>,
+    others: Query<(&Transform, &Player), Without<MainPlayer>>,
+) {
+    if bot.turn.tick(time.delta()).just_finished() {
+        bot.heading = Vec2::from_angle(fastrand::f32() * TAU);
+    }
+    let Ok((transform, me)) = main_player.get_single() else {
+        actions.set(Vec2::ZERO, HashSet::new());
+        return;
+    };
+    let position = transform.translation.truncate();
+    let position_of = |id: &ClientId| {
+        others
+            .iter()
+            .find(|(_, player)| player.id == *id)
+            .map(|(transform, _)| transform.translation.truncate())
+    };
+    let closest = others
+        .iter()
+        .map(|(transform, _)| transform.translation.truncate())
+        .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+
+    let mut pressed = HashSet::new();
+    let move_axis = match (&round.it, closest) {
+        (Some(it), Some(target)) if *it == me.id => {
+            if target.distance(position) < BOT_DASH_RANGE {
+                pressed.insert(Action::Dash);
+            }
+            (target - position).normalize_or_zero()
+        }
+        // Wandering a bit while fleeing so the chase doesn't end against a wall
+        (Some(it), _) => match position_of(it) {
+            Some(it) => {
+                ((position - it).normalize_or_zero() + bot.heading * 0.5).normalize_or_zero()
+            }
+            None => bot.heading,
+        },
+        _ => bot.heading,
+    };
+    actions.set(move_axis, pressed);
+}
+
+/// Server event an `expect` waits for, named as in the server's `client` scripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Expectation {
+    Start,
+    RoundStart,
+    RoundEnd,
+    Tag,
+    Chat,
+}
+
+impl Expectation {
+    const ALL: [Expectation; 5] = [
+        Expectation::Start,
+        Expectation::RoundStart,
+        Expectation::RoundEnd,
+        Expectation::Tag,
+        Expectation::Chat,
+    ];
+
+    /// Times the event came in so far
+    fn count(self, stats: &MatchStats) -> u32 {
+        match self {
+            Expectation::Start => stats.games,
+            Expectation::RoundStart => stats.rounds_started,
+            Expectation::RoundEnd => stats.rounds,
+            Expectation::Tag => stats.tags,
+            Expectation::Chat => stats.chat_messages,
+        }
+    }
+}
+
+enum Command {
+    Join,
+    Leave,
+    /// Holds the direction for a while, both between -1 and 1
+    Walk(Vec2, Duration),
+    Chat(String),
+    Emote(Emote),
+    Wait(Duration),
+    Expect(Expectation, Duration),
+    Quit,
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Command, String> {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.split_whitespace().collect::<Vec<_>>();
+        let ms = |arg: Option<&&str>, default: Option<Duration>| match arg {
+            Some(arg) => arg
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| format!("invalid duration {}", arg)),
+            None => default.ok_or_else(|| "missing duration".to_string()),
+        };
+        Ok(match command {
+            "join" => Command::Join,
+            "leave" => Command::Leave,
+            "quit" | "exit" => Command::Quit,
+            // Positions come from the physics, so there is no `move` sending one
+            "move" => return Err("use walk <x> <y> <ms> to move the player".to_string()),
+            "walk" => {
+                let (Some(Ok(x)), Some(Ok(y))) = (
+                    args.first().map(|x| x.parse::<f32>()),
+                    args.get(1).map(|y| y.parse::<f32>()),
+                ) else {
+                    return Err("expected walk <x> <y> <ms>".to_string());
+                };
+                Command::Walk(
+                    Vec2::new(x, y).clamp_length_max(1.0),
+                    ms(args.get(2), None)?,
+                )
+            }
+            "chat" => Command::Chat(line["chat".len()..].trim().to_string()),
+            "emote" => Command::Emote(match args.first().map(|name| name.to_lowercase()) {
+                Some(name) if name == "hello" => Emote::Hello,
+                Some(name) if name == "laugh" => Emote::Laugh,
+                Some(name) if name == "angry" => Emote::Angry,
+                Some(name) if name == "gg" => Emote::GoodGame,
+                _ => return Err("expected hello, laugh, angry or gg".to_string()),
+            }),
+            "wait" => Command::Wait(ms(args.first(), None)?),
+            "expect" => {
+                let expectation = args.first().and_then(|name| {
+                    Expectation::ALL
+                        .into_iter()
+                        .find(|expectation| format!("{:?}", expectation).eq_ignore_ascii_case(name))
+                });
+                let Some(expectation) = expectation else {
+                    return Err("expected Start, RoundStart, RoundEnd, Tag or Chat".to_string());
+                };
+                Command::Expect(expectation, ms(args.get(1), Some(DEFAULT_EXPECT_TIMEOUT))?)
+            }
+            _ => return Err(format!("unknown command {}", command)),
+        })
+    }
+}
+
+/// Command still running, such as a walk or an `expect`
+struct Pending {
+    line: usize,
+    command: Command,
+    timer: Timer,
+}
+
+/// Commands played in order instead of the bot, one per line with `#` comments.
+/// They follow the scripts of the server's `client` binary, except that `walk`
+/// holds a direction instead of `move` sending a position, and that `expect`
+/// only knows the events below. An `expect` is met by an event that came in
+/// since the last one met, so events arriving before the command aren't missed.
+///
+/// ```text
+/// join
+/// expect Start 5000
+/// walk 1 0 500
+/// emote gg
+/// chat hello
+/// wait 1000
+/// expect RoundEnd 130000
+/// leave
+/// ```
+#[derive(Resource)]
+struct Script {
+    /// Line numbers and commands still to run
+    commands: VecDeque<(usize, Command)>,
+    pending: Option<Pending>,
+    /// Events already used up by an `expect`
+    matched: HashMap<Expectation, u32>,
+}
+
+impl Script {
+    fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read script {}: {}", path.display(), e))?;
+        let mut commands = VecDeque::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let command = Command::parse(line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), index + 1, e))?;
+            commands.push_back((index + 1, command));
+        }
+        Ok(Self {
+            commands,
+            pending: None,
+            matched: HashMap::new(),
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_script(
+    time: Res<Time>,
+    mut script: ResMut<Script>,
+    mut actions: ResMut<ActionState>,
+    stats: Res<MatchStats>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut next_menu_state: ResMut<NextState<MenuState>>,
+    tcp_sender: Res<TcpSocketSender>,
+    udp_sender: Res<UdpSocketSender>,
+    main_player: Query<&Player, With<MainPlayer>>,
+    connection_state: Res<ConnectionState>,
+    mut commands: Commands,
+) {
+    // Paused while there is no connection to send commands over
+    if *connection_state != ConnectionState::Connected {
+        return;
+    }
+    let script = &mut *script;
+    if let Some(pending) = script.pending.as_mut() {
+        pending.timer.tick(time.delta());
+        let done = match pending.command {
+            Command::Expect(expectation, timeout) => {
+                let matched = script.matched.entry(expectation).or_default();
+                let met = expectation.count(&stats) > *matched;
+                if met {
+                    *matched += 1;
+                }
+                if !met && pending.timer.finished() {
+                    let failure = format!(
+                        "line {}: expected {:?} within {:?}",
+                        pending.line, expectation, timeout
+                    );
+                    commands.trigger(Finish(Some(failure)));
+                    return;
+                }
+                met
+            }
+            _ => pending.timer.finished(),
+        };
+        if !done {
+            return;
+        }
+        if let Command::Walk(..) = pending.command {
+            actions.set(Vec2::ZERO, HashSet::new());
+        }
+        script.pending = None;
+    }
+
+    while let Some((line, command)) = script.commands.pop_front() {
+        let duration = match command {
+            Command::Join => {
+                send(&tcp_sender.0, ClientEvent::Join);
+                next_menu_state.set(MenuState::Lobby);
+                continue;
+            }
+            Command::Leave => {
+                send(&tcp_sender.0, ClientEvent::Leave);
+                next_game_state.set(GameState::Menu);
+                next_menu_state.set(MenuState::Main);
+                continue;
+            }
+            Command::Chat(ref message) => {
+                let event = ClientEvent::Chat(ChatMessageEvent {
+                    message: message.clone(),
+                });
+                send(&tcp_sender.0, event);
+                continue;
+            }
+            Command::Emote(emote) => {
+                let Ok(player) = main_player.get_single() else {
+                    let failure = format!("line {}: emotes need a game", line);
+                    commands.trigger(Finish(Some(failure)));
+                    return;
+                };
+                let event = ClientEvent::Emote(EmoteEvent {
+                    client_id: player.id.clone(),
+                    emote,
+                });
+                send(&udp_sender.0, event);
+                continue;
+            }
+            Command::Quit => break,
+            Command::Walk(direction, duration) => {
+                actions.set(direction, HashSet::new());
+                duration
+            }
+            Command::Wait(duration) | Command::Expect(_, duration) => duration,
+        };
+        script.pending = Some(Pending {
+            line,
+            command,
+            timer: Timer::new(duration, TimerMode::Once),
+        });
+        return;
+    }
+    commands.trigger(Finish(None));
+}
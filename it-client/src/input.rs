@@ -0,0 +1,215 @@
+use crate::settings::{KeyAction, Settings};
+use bevy::input::gamepad::{GamepadAxisType, GamepadButtonType};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use it_core::{Emote, InputState};
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActionState>()
+            .init_resource::<TextFocus>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem));
+    }
+}
+
+/// Discrete actions, movement goes through [`ActionState::move_axis`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Dash,
+    Emote1,
+    Emote2,
+    Emote3,
+    Emote4,
+    Scoreboard,
+    Pause,
+    Minimap,
+    CameraMode,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Dash,
+        Action::Emote1,
+        Action::Emote2,
+        Action::Emote3,
+        Action::Emote4,
+        Action::Scoreboard,
+        Action::Pause,
+        Action::Minimap,
+        Action::CameraMode,
+    ];
+
+    fn key_action(self) -> KeyAction {
+        match self {
+            Action::Dash => KeyAction::Dash,
+            Action::Emote1 => KeyAction::Emote1,
+            Action::Emote2 => KeyAction::Emote2,
+            Action::Emote3 => KeyAction::Emote3,
+            Action::Emote4 => KeyAction::Emote4,
+            Action::Scoreboard => KeyAction::Scoreboard,
+            Action::Pause => KeyAction::Pause,
+            Action::Minimap => KeyAction::Minimap,
+            Action::CameraMode => KeyAction::CameraMode,
+        }
+    }
+
+    pub fn emote(self) -> Option<Emote> {
+        match self {
+            Action::Emote1 => Some(Emote::Hello),
+            Action::Emote2 => Some(Emote::Laugh),
+            Action::Emote3 => Some(Emote::Angry),
+            Action::Emote4 => Some(Emote::GoodGame),
+            _ => None,
+        }
+    }
+
+    fn gamepad_button(self) -> GamepadButtonType {
+        match self {
+            Action::Dash => GamepadButtonType::South,
+            Action::Emote1 => GamepadButtonType::West,
+            Action::Emote2 => GamepadButtonType::East,
+            Action::Emote3 => GamepadButtonType::LeftTrigger,
+            Action::Emote4 => GamepadButtonType::RightTrigger,
+            Action::Scoreboard => GamepadButtonType::Select,
+            Action::Pause => GamepadButtonType::Start,
+            Action::Minimap => GamepadButtonType::North,
+            Action::CameraMode => GamepadButtonType::RightThumb,
+        }
+    }
+}
+
+/// Set while a text field such as the chat captures the keyboard
+#[derive(Resource, Default, PartialEq)]
+pub struct TextFocus(pub bool);
+
+/// Actions for the current frame, merged from the keyboard and every gamepad
+#[derive(Resource, Default)]
+pub struct ActionState {
+    move_axis: Vec2,
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    /// Keyboard actions held while typing, ignored until their keys are released
+    blocked: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn move_axis(&self) -> Vec2 {
+        self.move_axis
+    }
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+    /// Network and replay representation of the held actions
+    pub fn snapshot(&self) -> InputState {
+        InputState {
+            move_x: self.move_axis.x,
+            move_y: self.move_axis.y,
+            dash: self.pressed(Action::Dash),
+            emote: self.pressed.iter().any(|action| action.emote().is_some()),
+        }
+    }
+    /// Replaces the actions held this frame, used by the input devices and by
+    /// the headless client's bot
+    pub fn set(&mut self, move_axis: Vec2, pressed: HashSet<Action>) {
+        self.just_pressed = pressed.difference(&self.pressed).copied().collect();
+        self.move_axis = move_axis.clamp_length_max(1.0);
+        self.pressed = pressed;
+    }
+}
+
+/// Zeroes the stick inside the deadzone and rescales the rest to 0..1
+fn apply_deadzone(stick: Vec2, deadzone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= deadzone || deadzone >= 1.0 {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+    stick / length * scaled
+}
+
+fn update_action_state(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    settings: Res<Settings>,
+    text_focus: Res<TextFocus>,
+    mut actions: ResMut<ActionState>,
+) {
+    let mut keys = Vec2::ZERO;
+    if settings.pressed(KeyAction::MoveUp, &keyboard_input) {
+        keys.y += 1.0;
+    }
+    if settings.pressed(KeyAction::MoveDown, &keyboard_input) {
+        keys.y -= 1.0;
+    }
+    if settings.pressed(KeyAction::MoveLeft, &keyboard_input) {
+        keys.x -= 1.0;
+    }
+    if settings.pressed(KeyAction::MoveRight, &keyboard_input) {
+        keys.x += 1.0;
+    }
+    let mut move_axis = keys.normalize_or_zero();
+
+    let keyboard_pressed = Action::ALL
+        .into_iter()
+        .filter(|action| settings.pressed(action.key_action(), &keyboard_input))
+        .collect::<HashSet<_>>();
+    if text_focus.0 {
+        actions.blocked.extend(keyboard_pressed.iter().copied());
+    }
+    actions
+        .blocked
+        .retain(|action| keyboard_pressed.contains(action));
+    let mut pressed = keyboard_pressed
+        .difference(&actions.blocked)
+        .copied()
+        .collect::<HashSet<_>>();
+
+    for gamepad in gamepads.iter() {
+        let stick = Vec2::new(
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or(0.0),
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+                .unwrap_or(0.0),
+        );
+        move_axis += apply_deadzone(stick, settings.gamepad_deadzone);
+
+        let button_pressed = |button| gamepad_buttons.pressed(GamepadButton::new(gamepad, button));
+        let mut dpad = Vec2::ZERO;
+        if button_pressed(GamepadButtonType::DPadUp) {
+            dpad.y += 1.0;
+        }
+        if button_pressed(GamepadButtonType::DPadDown) {
+            dpad.y -= 1.0;
+        }
+        if button_pressed(GamepadButtonType::DPadLeft) {
+            dpad.x -= 1.0;
+        }
+        if button_pressed(GamepadButtonType::DPadRight) {
+            dpad.x += 1.0;
+        }
+        move_axis += dpad.normalize_or_zero();
+
+        for action in Action::ALL {
+            if button_pressed(action.gamepad_button()) {
+                pressed.insert(action);
+            }
+        }
+    }
+
+    // Nothing moves the player while typing, gamepads included
+    if text_focus.0 {
+        move_axis = Vec2::ZERO;
+        pressed.clear();
+    }
+    actions.set(move_axis, pressed);
+}
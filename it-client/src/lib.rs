@@ -0,0 +1,36 @@
+//! Game client, built as a library so tests can run its plugins. `main.rs`
+//! opens the window or runs it headless.
+
+use bevy::prelude::*;
+
+pub mod animation;
+pub mod audio;
+pub mod camera;
+pub mod chat;
+#[cfg(feature = "dev")]
+pub mod dev;
+pub mod editor;
+pub mod game;
+pub mod headless;
+pub mod input;
+pub mod menu;
+pub mod net;
+pub mod net_debug;
+pub mod net_sim;
+pub mod player;
+pub mod replay;
+pub mod settings;
+
+#[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    Menu,
+    Game,
+    Editor,
+}
+
+pub fn cleanup_entities<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
+    for entity in &to_despawn {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,52 +1,96 @@
+use bevy::audio::{AudioPlugin, SpatialScale};
 use bevy::prelude::*;
-use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_rapier2d::prelude::*;
-use camera::CameraPlugin;
-use menu::MenuPlugin;
-use player::PlayerPlugin;
-
-use self::net::NetworkPlugin;
-
-pub mod animation;
-pub mod camera;
-pub mod game;
-pub mod menu;
-pub mod net;
-pub mod player;
-
-#[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
-pub enum GameState {
-    #[default]
-    Menu,
-    Game,
-}
+use it_client::animation::AnimationPlugin;
+use it_client::audio::SoundPlugin;
+use it_client::camera::CameraPlugin;
+use it_client::chat::ChatPlugin;
+use it_client::editor::{EditorPlugin, MapsDir};
+use it_client::game::GamePlugin;
+use it_client::headless::{self, HeadlessConfig};
+use it_client::input::InputPlugin;
+use it_client::menu::MenuPlugin;
+use it_client::net::{NetworkPlugin, ServerAddr};
+use it_client::net_debug::NetDebugPlugin;
+use it_client::net_sim::NetSimConfig;
+use it_client::player::PlayerPlugin;
+use it_client::replay::{RecordReplays, ReplayPlugin};
+use it_client::settings::{Settings, SettingsPlugin};
+use it_client::GameState;
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut server_addr = ServerAddr::default();
+    server_addr.apply_args(&args);
+    if let Some(config) = HeadlessConfig::from_args(&args) {
+        // Saved settings are left out so runs are the same on every machine
+        let mut network_simulation = NetSimConfig::default();
+        network_simulation.apply_args(&args);
+        let exit = headless::run(config, network_simulation, server_addr);
+        std::process::exit(match exit {
+            AppExit::Success => 0,
+            AppExit::Error(code) => code.get().into(),
+        });
+    }
+
+    let settings = Settings::load();
+    // Kept apart from the settings so flags given once are not saved
+    let mut network_simulation = settings.network_simulation;
+    network_simulation.apply_args(&args);
+    let mut maps_dir = MapsDir::default();
+    maps_dir.apply_args(&args);
+    let mut record_replays = RecordReplays::default();
+    record_replays.apply_args(&args);
     let mut app = App::new();
     app.add_plugins((
         DefaultPlugins
             .set(ImagePlugin::default_nearest())
+            .set(AssetPlugin {
+                // Hot reload assets such as animation sheets while developing
+                watch_for_changes_override: Some(cfg!(feature = "dev")),
+                ..default()
+            })
+            .set(AudioPlugin {
+                // Sounds fade out over roughly a screen width
+                default_spatial_scale: SpatialScale::new_2d(1.0 / 400.0),
+                ..default()
+            })
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "It".to_string(),
                     name: Some("main".to_string()),
                     resizable: true,
-                    present_mode: bevy::window::PresentMode::AutoNoVsync,
+                    present_mode: settings.present_mode(),
+                    mode: settings.window_mode.into(),
                     ..default()
                 }),
                 ..default()
             }),
-        WorldInspectorPlugin::new(),
         RapierPhysicsPlugin::<()>::pixels_per_meter(32.0),
-        RapierDebugRenderPlugin::default(),
     ))
-    .add_plugins((CameraPlugin, NetworkPlugin, MenuPlugin, PlayerPlugin))
-    .init_state::<GameState>()
-    .run();
-}
-
-pub fn cleanup_entities<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
-    for entity in &to_despawn {
-        commands.entity(entity).despawn_recursive();
-    }
+    .add_plugins((
+        CameraPlugin,
+        NetworkPlugin,
+        MenuPlugin,
+        PlayerPlugin,
+        GamePlugin,
+        AnimationPlugin,
+        EditorPlugin,
+        SettingsPlugin,
+        InputPlugin,
+        ReplayPlugin,
+        SoundPlugin,
+        ChatPlugin,
+        NetDebugPlugin,
+    ))
+    .insert_resource(GlobalVolume::new(settings.master_volume))
+    .insert_resource(settings)
+    .insert_resource(network_simulation)
+    .insert_resource(server_addr)
+    .insert_resource(maps_dir)
+    .insert_resource(record_replays)
+    .init_state::<GameState>();
+    #[cfg(feature = "dev")]
+    app.add_plugins(it_client::dev::DevToolsPlugin);
+    app.run();
 }
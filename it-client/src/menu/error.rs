@@ -0,0 +1,88 @@
+use crate::cleanup_entities;
+use crate::net::{ConnectEvent, ConnectionState};
+use bevy::prelude::*;
+
+use super::{GenericButton, InteractionQuery, MenuState};
+
+pub struct ErrorPlugin;
+
+impl Plugin for ErrorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(MenuState::Error), setup_error)
+            .add_systems(Update, menu_interaction.run_if(in_state(MenuState::Error)))
+            .add_systems(OnExit(MenuState::Error), cleanup_entities::<OnErrorScreen>);
+    }
+}
+
+#[derive(Component)]
+enum ErrorButtonAction {
+    Retry,
+    Back,
+}
+
+#[derive(Component)]
+struct OnErrorScreen;
+
+fn menu_interaction(
+    interaction_query: InteractionQuery<ErrorButtonAction>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut commands: Commands,
+) {
+    for (interaction, button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match button_action {
+                ErrorButtonAction::Retry => {
+                    commands.trigger(ConnectEvent);
+                    menu_state.set(MenuState::Main);
+                }
+                ErrorButtonAction::Back => {
+                    menu_state.set(MenuState::Main);
+                }
+            }
+        }
+    }
+}
+fn setup_error(mut commands: Commands, connection_state: Res<ConnectionState>) {
+    let reason = match &*connection_state {
+        ConnectionState::Failed(reason) => reason.clone(),
+        _ => "Not connected to the server".to_string(),
+    };
+    // Root node
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(20.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+            OnErrorScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Could not connect to the server",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            parent.spawn(TextBundle::from_section(
+                reason,
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::srgb(1.0, 0.4, 0.4),
+                    ..default()
+                },
+            ));
+            parent.spawn(GenericButton::new("Retry", ErrorButtonAction::Retry));
+            parent.spawn(GenericButton::new("Back", ErrorButtonAction::Back));
+        });
+}
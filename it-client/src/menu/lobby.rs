@@ -1,7 +1,10 @@
 use crate::cleanup_entities;
+use crate::net::TcpSocketSender;
 use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use it_core::ClientEvent;
 
-use super::{GenericButton, MenuState};
+use super::{GenericButton, InteractionQuery, MenuState};
 
 pub struct LobbyPlugin;
 
@@ -22,16 +25,20 @@ enum LobbyButtonAction {
 struct OnLobbyScreen;
 
 fn menu_interaction(
-    interaction_query: Query<
-        (&Interaction, &LobbyButtonAction),
-        (Changed<Interaction>, With<Button>),
-    >,
+    interaction_query: InteractionQuery<LobbyButtonAction>,
+    socket_sender: Res<TcpSocketSender>,
     mut menu_state: ResMut<NextState<MenuState>>,
 ) {
     for (interaction, button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match button_action {
                 LobbyButtonAction::Leave => {
+                    let socket_sender = socket_sender.0.clone();
+                    IoTaskPool::get()
+                        .spawn(async move {
+                            let _ = socket_sender.send(ClientEvent::Leave).await;
+                        })
+                        .detach();
                     menu_state.set(MenuState::Main);
                 }
             }
@@ -1,21 +1,32 @@
-use crate::net::TcpSocketSender;
+use crate::net::{ConnectionState, Reconnect, TcpSocketSender};
 use crate::{cleanup_entities, GameState};
 use bevy::ecs::component::{ComponentHooks, StorageType};
 use bevy::prelude::*;
 use bevy::tasks::IoTaskPool;
 use it_core::ClientEvent;
 
+pub mod error;
 pub mod lobby;
+pub mod pause;
+pub mod settings;
 
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<MenuState>()
-            .add_plugins(lobby::LobbyPlugin)
+            .add_plugins((
+                lobby::LobbyPlugin,
+                error::ErrorPlugin,
+                pause::PausePlugin,
+                settings::SettingsMenuPlugin,
+            ))
             .add_systems(OnEnter(GameState::Menu), setup_menu)
             .add_systems(OnEnter(MenuState::Main), main_menu_setup)
-            .add_systems(Update, menu_interaction.run_if(in_state(MenuState::Main)))
+            .add_systems(
+                Update,
+                (menu_interaction, update_connection_status).run_if(in_state(MenuState::Main)),
+            )
             .add_systems(
                 OnExit(MenuState::Main),
                 cleanup_entities::<OnMainMenuScreen>,
@@ -27,25 +38,36 @@ pub enum MenuState {
     #[default]
     Main,
     Lobby,
+    Error,
+    Settings,
+    Pause,
     Disabled,
 }
 
+/// Buttons whose interaction changed this frame, with the action they trigger
+type InteractionQuery<'w, 's, T> =
+    Query<'w, 's, (&'static Interaction, &'static T), (Changed<Interaction>, With<Button>)>;
+
 #[derive(Component)]
 pub struct OnMainMenuScreen;
 
+#[derive(Component)]
+struct ConnectionStatusText;
+
 #[derive(Component)]
 enum MenuButtonAction {
     Play,
+    Editor,
+    Settings,
     Quit,
 }
 
 fn menu_interaction(
-    interaction_query: Query<
-        (&Interaction, &MenuButtonAction),
-        (Changed<Interaction>, With<Button>),
-    >,
-    mut socket_sender: ResMut<TcpSocketSender>,
+    interaction_query: InteractionQuery<MenuButtonAction>,
+    socket_sender: Res<TcpSocketSender>,
+    connection_state: Res<ConnectionState>,
     mut menu_state: ResMut<NextState<MenuState>>,
+    mut game_state: ResMut<NextState<GameState>>,
     mut exit: EventWriter<AppExit>,
 ) {
     let task_pool = IoTaskPool::get();
@@ -54,6 +76,14 @@ fn menu_interaction(
         if *interaction == Interaction::Pressed {
             match button_action {
                 MenuButtonAction::Play => {
+                    match *connection_state {
+                        ConnectionState::Connected => {}
+                        ConnectionState::Failed(_) => {
+                            menu_state.set(MenuState::Error);
+                            continue;
+                        }
+                        _ => continue,
+                    }
                     task_pool
                         .spawn(async move {
                             let _ = socket_sender.send(ClientEvent::Join).await;
@@ -61,6 +91,13 @@ fn menu_interaction(
                         .detach();
                     menu_state.set(MenuState::Lobby);
                 }
+                MenuButtonAction::Editor => {
+                    game_state.set(GameState::Editor);
+                    menu_state.set(MenuState::Disabled);
+                }
+                MenuButtonAction::Settings => {
+                    menu_state.set(MenuState::Settings);
+                }
                 MenuButtonAction::Quit => {
                     exit.send(AppExit::Success);
                 }
@@ -111,6 +148,23 @@ fn main_menu_setup(mut commands: Commands) {
                         },
                     ));
                 });
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    left: Val::Percent(5.0),
+                    ..default()
+                }),
+                ConnectionStatusText,
+            ));
             // Column for buttons
             parent
                 .spawn(NodeBundle {
@@ -119,17 +173,44 @@ fn main_menu_setup(mut commands: Commands) {
                         justify_content: JustifyContent::SpaceEvenly,
                         align_items: AlignItems::Center,
                         width: Val::Percent(30.0),
-                        height: Val::Percent(30.0),
+                        height: Val::Percent(40.0),
                         ..default()
                     },
                     ..default()
                 })
                 .with_children(|parent| {
                     parent.spawn(GenericButton::new("Play", MenuButtonAction::Play));
+                    parent.spawn(GenericButton::new("Editor", MenuButtonAction::Editor));
+                    parent.spawn(
+                        GenericButton::new("Settings", MenuButtonAction::Settings)
+                            .with_width(Val::Px(200.0)),
+                    );
                     parent.spawn(GenericButton::new("Quit", MenuButtonAction::Quit));
                 });
         });
 }
+fn update_connection_status(
+    connection_state: Res<ConnectionState>,
+    reconnect: Res<Reconnect>,
+    mut text_query: Query<&mut Text, With<ConnectionStatusText>>,
+) {
+    let status = match (&*connection_state, &reconnect.timer) {
+        (_, Some(timer)) => format!(
+            "Reconnecting in {:.0}s (attempt {}/{})",
+            timer.remaining_secs().ceil(),
+            reconnect.attempt,
+            reconnect.max_attempts()
+        ),
+        (ConnectionState::Disconnected, None) => "Disconnected".to_string(),
+        (ConnectionState::Connecting, None) => "Connecting...".to_string(),
+        (ConnectionState::Connected, None) => "Connected".to_string(),
+        (ConnectionState::Failed(reason), None) => format!("Connection failed: {}", reason),
+    };
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value.clone_from(&status);
+    }
+}
+
 fn setup_menu(mut menu_state: ResMut<NextState<MenuState>>) {
     menu_state.set(MenuState::Main);
 }
@@ -178,6 +259,14 @@ impl<T: Component> GenericButton<T> {
         self.button_bundle.style.height = height;
         self
     }
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        if let Some(text_bundle) = self.text_bundle.0.as_mut() {
+            for section in text_bundle.text.sections.iter_mut() {
+                section.style.font_size = font_size;
+            }
+        }
+        self
+    }
 }
 pub struct BundleChild<B: Bundle>(Option<B>);
 
@@ -0,0 +1,116 @@
+use crate::input::{Action, ActionState};
+use crate::net::TcpSocketSender;
+use crate::{cleanup_entities, GameState};
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use it_core::ClientEvent;
+
+use super::{GenericButton, InteractionQuery, MenuState};
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(MenuState::Pause), setup_pause)
+            .add_systems(Update, toggle_pause.run_if(in_state(GameState::Game)))
+            .add_systems(Update, menu_interaction.run_if(in_state(MenuState::Pause)))
+            .add_systems(OnExit(MenuState::Pause), cleanup_entities::<OnPauseScreen>);
+    }
+}
+
+#[derive(Component)]
+enum PauseButtonAction {
+    Resume,
+    Settings,
+    Leave,
+}
+
+#[derive(Component)]
+struct OnPauseScreen;
+
+/// The match keeps running on the server, pausing only opens the menu
+fn toggle_pause(
+    actions: Res<ActionState>,
+    current_menu_state: Res<State<MenuState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+) {
+    if !actions.just_pressed(Action::Pause) {
+        return;
+    }
+    match current_menu_state.get() {
+        MenuState::Disabled => menu_state.set(MenuState::Pause),
+        MenuState::Pause => menu_state.set(MenuState::Disabled),
+        _ => {}
+    }
+}
+
+fn menu_interaction(
+    interaction_query: InteractionQuery<PauseButtonAction>,
+    socket_sender: Res<TcpSocketSender>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match button_action {
+                PauseButtonAction::Resume => {
+                    menu_state.set(MenuState::Disabled);
+                }
+                PauseButtonAction::Settings => {
+                    menu_state.set(MenuState::Settings);
+                }
+                PauseButtonAction::Leave => {
+                    let socket_sender = socket_sender.0.clone();
+                    IoTaskPool::get()
+                        .spawn(async move {
+                            let _ = socket_sender.send(ClientEvent::Leave).await;
+                        })
+                        .detach();
+                    game_state.set(GameState::Menu);
+                }
+            }
+        }
+    }
+}
+fn setup_pause(mut commands: Commands) {
+    // Root node
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(20.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: Color::BLACK.with_alpha(0.6).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            OnPauseScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            parent.spawn(
+                GenericButton::new("Resume", PauseButtonAction::Resume).with_width(Val::Px(250.0)),
+            );
+            parent.spawn(
+                GenericButton::new("Settings", PauseButtonAction::Settings)
+                    .with_width(Val::Px(250.0)),
+            );
+            parent.spawn(
+                GenericButton::new("Leave Game", PauseButtonAction::Leave)
+                    .with_width(Val::Px(250.0)),
+            );
+        });
+}
@@ -0,0 +1,377 @@
+use crate::cleanup_entities;
+use crate::settings::{key_label, KeyAction, Settings, FPS_CAPS};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+
+use super::{GenericButton, InteractionQuery, MenuState};
+
+const TEXT_SIZE: f32 = 20.0;
+const SLIDER_WIDTH: f32 = 200.0;
+
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AwaitingBinding>()
+            .add_systems(OnEnter(MenuState::Settings), setup_settings)
+            .add_systems(
+                Update,
+                (
+                    menu_interaction,
+                    capture_binding,
+                    drag_volume_sliders,
+                    update_settings_labels,
+                )
+                    .run_if(in_state(MenuState::Settings)),
+            )
+            .add_systems(
+                OnExit(MenuState::Settings),
+                (cleanup_entities::<OnSettingsScreen>, save_settings),
+            );
+    }
+}
+
+#[derive(Component)]
+struct OnSettingsScreen;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingSlot {
+    Primary,
+    Secondary,
+}
+
+#[derive(Component)]
+enum SettingsButtonAction {
+    Rebind(KeyAction, BindingSlot),
+    ToggleVsync,
+    CycleWindowMode,
+    CycleFpsCap,
+    CycleMinimapCorner,
+    Back,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Volume {
+    Master,
+    Music,
+    Effects,
+}
+
+impl Volume {
+    fn label(self) -> &'static str {
+        match self {
+            Volume::Master => "Master volume",
+            Volume::Music => "Music volume",
+            Volume::Effects => "Effects volume",
+        }
+    }
+    fn value(self, settings: &Settings) -> f32 {
+        match self {
+            Volume::Master => settings.master_volume,
+            Volume::Music => settings.music_volume,
+            Volume::Effects => settings.effects_volume,
+        }
+    }
+    fn value_mut(self, settings: &mut Settings) -> &mut f32 {
+        match self {
+            Volume::Master => &mut settings.master_volume,
+            Volume::Music => &mut settings.music_volume,
+            Volume::Effects => &mut settings.effects_volume,
+        }
+    }
+}
+
+#[derive(Component)]
+struct VolumeSlider(Volume);
+
+#[derive(Component)]
+struct VolumeFill(Volume);
+
+/// Key binding slot waiting for the next key press
+#[derive(Resource, Default)]
+struct AwaitingBinding(Option<(KeyAction, BindingSlot)>);
+
+fn menu_interaction(
+    interaction_query: InteractionQuery<SettingsButtonAction>,
+    mut settings: ResMut<Settings>,
+    mut awaiting: ResMut<AwaitingBinding>,
+    game_state: Res<State<GameState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+) {
+    for (interaction, button_action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match button_action {
+                SettingsButtonAction::Rebind(action, slot) => {
+                    awaiting.0 = Some((*action, *slot));
+                }
+                SettingsButtonAction::ToggleVsync => {
+                    settings.vsync = !settings.vsync;
+                }
+                SettingsButtonAction::CycleWindowMode => {
+                    settings.window_mode = settings.window_mode.next();
+                }
+                SettingsButtonAction::CycleFpsCap => {
+                    let current = FPS_CAPS
+                        .iter()
+                        .position(|cap| *cap == settings.fps_cap)
+                        .unwrap_or(0);
+                    settings.fps_cap = FPS_CAPS[(current + 1) % FPS_CAPS.len()];
+                }
+                SettingsButtonAction::CycleMinimapCorner => {
+                    settings.minimap_corner = settings.minimap_corner.next();
+                }
+                SettingsButtonAction::Back => {
+                    awaiting.0 = None;
+                    if *game_state.get() == GameState::Game {
+                        menu_state.set(MenuState::Pause);
+                    } else {
+                        menu_state.set(MenuState::Main);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Binds the next pressed key, Escape cancels and Backspace clears the slot
+fn capture_binding(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut awaiting: ResMut<AwaitingBinding>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some((action, slot)) = awaiting.0 else {
+        return;
+    };
+    let Some(key) = keyboard_input.get_just_pressed().next().copied() else {
+        return;
+    };
+    awaiting.0 = None;
+    let key = match key {
+        KeyCode::Escape => return,
+        KeyCode::Backspace => None,
+        key => Some(key),
+    };
+    let mut binding = settings.binding(action);
+    match slot {
+        BindingSlot::Primary => binding.primary = key,
+        BindingSlot::Secondary => binding.secondary = key,
+    }
+    settings.key_bindings.insert(action, binding);
+}
+
+fn drag_volume_sliders(
+    slider_query: Query<(&Interaction, &RelativeCursorPosition, &VolumeSlider)>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, cursor, slider) in &slider_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(position) = cursor.normalized {
+            let volume = (position.x.clamp(0.0, 1.0) * 20.0).round() / 20.0;
+            if slider.0.value(&settings) != volume {
+                *slider.0.value_mut(&mut settings) = volume;
+            }
+        }
+    }
+}
+
+fn update_settings_labels(
+    settings: Res<Settings>,
+    awaiting: Res<AwaitingBinding>,
+    button_query: Query<(&SettingsButtonAction, &Children)>,
+    mut text_query: Query<&mut Text>,
+    new_labels: Query<(), Added<Text>>,
+    mut fill_query: Query<(&VolumeFill, &mut Style)>,
+) {
+    if !settings.is_changed() && !awaiting.is_changed() && new_labels.is_empty() {
+        return;
+    }
+    for (button_action, children) in &button_query {
+        let label = match button_action {
+            SettingsButtonAction::Rebind(action, slot) => {
+                if awaiting.0 == Some((*action, *slot)) {
+                    "...".to_string()
+                } else {
+                    let binding = settings.binding(*action);
+                    match slot {
+                        BindingSlot::Primary => key_label(binding.primary),
+                        BindingSlot::Secondary => key_label(binding.secondary),
+                    }
+                }
+            }
+            SettingsButtonAction::ToggleVsync => {
+                format!("VSync: {}", if settings.vsync { "On" } else { "Off" })
+            }
+            SettingsButtonAction::CycleWindowMode => settings.window_mode.label().to_string(),
+            SettingsButtonAction::CycleFpsCap => match settings.fps_cap {
+                Some(cap) => format!("FPS cap: {}", cap),
+                None => "FPS cap: Off".to_string(),
+            },
+            SettingsButtonAction::CycleMinimapCorner => {
+                format!("Minimap: {}", settings.minimap_corner.label())
+            }
+            SettingsButtonAction::Back => continue,
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.sections[0].value.clone_from(&label);
+            }
+        }
+    }
+    for (fill, mut style) in fill_query.iter_mut() {
+        style.width = Val::Percent(fill.0.value(&settings) * 100.0);
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    settings.save();
+}
+
+fn text(value: impl Into<String>) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size: TEXT_SIZE,
+            color: Color::WHITE,
+            ..default()
+        },
+    )
+}
+
+fn row() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(10.0),
+            ..default()
+        },
+        ..default()
+    }
+}
+
+fn setup_settings(mut commands: Commands, game_state: Res<State<GameState>>) {
+    // Translucent in game so the match stays visible behind
+    let background = if *game_state.get() == GameState::Game {
+        Color::BLACK.with_alpha(0.85)
+    } else {
+        Color::BLACK
+    };
+    // Root node
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(8.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: background.into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            OnSettingsScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Settings",
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ));
+            // Two columns of bindings so everything fits on small windows
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        display: Display::Grid,
+                        grid_template_columns: RepeatedGridTrack::auto(2),
+                        column_gap: Val::Px(30.0),
+                        row_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for action in KeyAction::ALL {
+                        parent.spawn(row()).with_children(|parent| {
+                            parent.spawn(text(action.label()).with_style(Style {
+                                width: Val::Px(150.0),
+                                ..default()
+                            }));
+                            for slot in [BindingSlot::Primary, BindingSlot::Secondary] {
+                                parent.spawn(
+                                    GenericButton::new(
+                                        "",
+                                        SettingsButtonAction::Rebind(action, slot),
+                                    )
+                                    .with_width(Val::Px(110.0))
+                                    .with_height(Val::Px(28.0))
+                                    .with_font_size(TEXT_SIZE),
+                                );
+                            }
+                        });
+                    }
+                });
+            parent.spawn(text("Escape cancels, Backspace clears a binding"));
+            parent.spawn(row()).with_children(|parent| {
+                for action in [
+                    SettingsButtonAction::ToggleVsync,
+                    SettingsButtonAction::CycleWindowMode,
+                    SettingsButtonAction::CycleFpsCap,
+                    SettingsButtonAction::CycleMinimapCorner,
+                ] {
+                    parent.spawn(
+                        GenericButton::new("", action)
+                            .with_width(Val::Px(190.0))
+                            .with_height(Val::Px(28.0))
+                            .with_font_size(TEXT_SIZE),
+                    );
+                }
+            });
+            for volume in [Volume::Master, Volume::Music, Volume::Effects] {
+                parent.spawn(row()).with_children(|parent| {
+                    parent.spawn(text(volume.label()).with_style(Style {
+                        width: Val::Px(150.0),
+                        ..default()
+                    }));
+                    parent
+                        .spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(SLIDER_WIDTH),
+                                    height: Val::Px(16.0),
+                                    ..default()
+                                },
+                                background_color: Color::srgb(0.3, 0.3, 0.3).into(),
+                                ..default()
+                            },
+                            Interaction::default(),
+                            RelativeCursorPosition::default(),
+                            VolumeSlider(volume),
+                        ))
+                        .with_children(|parent| {
+                            parent.spawn((
+                                NodeBundle {
+                                    style: Style {
+                                        height: Val::Percent(100.0),
+                                        ..default()
+                                    },
+                                    background_color: Color::WHITE.into(),
+                                    ..default()
+                                },
+                                VolumeFill(volume),
+                            ));
+                        });
+                });
+            }
+            parent.spawn(GenericButton::new("Back", SettingsButtonAction::Back));
+        });
+}
@@ -1,17 +1,35 @@
+use crate::chat::ChatReceivedEvent;
+use crate::game::effects::PlayerTaggedEvent;
+use crate::game::emotes::EmoteReceivedEvent;
+use crate::game::map::SpawnMapEvent;
+use crate::game::{Arena, Round, RoundEndEvent, RoundStartEvent, Scoreboard};
 use crate::menu::MenuState;
+use crate::net_sim::{self, NetSimConfig, NetworkConditioner};
 use crate::player::{Player, SpawnPlayerEvent};
 use crate::GameState;
 use async_channel::{unbounded, Receiver, Sender};
-use async_net::{TcpStream, UdpSocket};
-use bevy::asset::AsyncWriteExt;
 use bevy::prelude::*;
-use bevy::tasks::futures_lite::io::BufReader;
-use bevy::tasks::futures_lite::AsyncBufReadExt;
+use bevy::tasks::futures_lite::{future, FutureExt};
 use bevy::tasks::IoTaskPool;
-use it_core::{ClientEvent, ServerEvent, UdpUpgradeEvent};
+use it_core::transport::{self, MemoryNetwork, ReliableReceiver, ReliableSender, UnreliableSocket};
+use it_core::{ClientEvent, PingEvent, PlayerScore, ServerEvent, UdpUpgradeEvent};
+use std::collections::VecDeque;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+const BASE_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Time between two UDP probes used to measure latency and packet loss
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+/// Probes not echoed back within this delay count as lost
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of probes packet loss is computed over
+const PROBE_WINDOW: usize = 20;
+
 #[derive(Resource)]
 pub struct TcpSocketSender(pub Sender<ClientEvent>);
 
@@ -24,22 +42,325 @@ pub struct UdpSocketSender(pub Sender<ClientEvent>);
 #[derive(Resource)]
 pub struct UdpSocketReceiver(pub Receiver<ServerEvent>);
 
+/// Packets and bytes that went through one direction of a socket
+#[derive(Default)]
+pub struct ChannelCounter {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl ChannelCounter {
+    fn record(&self, bytes: usize) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+    pub fn packets(&self) -> u64 {
+        self.packets.load(Ordering::Relaxed)
+    }
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Traffic counters updated by the socket tasks
+#[derive(Default)]
+pub struct TrafficCounters {
+    pub tcp_sent: ChannelCounter,
+    pub tcp_received: ChannelCounter,
+    pub udp_sent: ChannelCounter,
+    pub udp_received: ChannelCounter,
+}
+
+#[derive(Resource, Default, Clone)]
+pub struct NetCounters(pub Arc<TrafficCounters>);
+
+/// Connection quality measured with UDP probes echoed by the server
+#[derive(Resource)]
+pub struct NetStats {
+    /// Round trip time of the last echoed probe
+    pub rtt_ms: Option<f32>,
+    /// Smoothed variation between consecutive round trip times
+    pub jitter_ms: f32,
+    /// Snapshots queued when the UDP events were last drained
+    pub snapshot_depth: usize,
+    probe_timer: Timer,
+    /// Send times of the probes still waiting for an echo
+    pending_probes: Vec<u64>,
+    /// Whether each of the last probes came back
+    probe_results: VecDeque<bool>,
+}
+
+impl Default for NetStats {
+    fn default() -> Self {
+        Self {
+            rtt_ms: None,
+            jitter_ms: 0.0,
+            snapshot_depth: 0,
+            probe_timer: Timer::new(PROBE_INTERVAL, TimerMode::Repeating),
+            pending_probes: Vec::new(),
+            probe_results: VecDeque::new(),
+        }
+    }
+}
+
+impl NetStats {
+    /// Share of lost probes, between 0 and 1
+    pub fn packet_loss(&self) -> Option<f32> {
+        if self.probe_results.is_empty() {
+            return None;
+        }
+        let lost = self.probe_results.iter().filter(|echoed| !**echoed).count();
+        Some(lost as f32 / self.probe_results.len() as f32)
+    }
+    fn record_probe(&mut self, echoed: bool) {
+        self.probe_results.push_back(echoed);
+        if self.probe_results.len() > PROBE_WINDOW {
+            self.probe_results.pop_front();
+        }
+    }
+    fn on_pong(&mut self, sent_at: u64, now: u64) {
+        let Some(index) = self.pending_probes.iter().position(|p| *p == sent_at) else {
+            // Late or duplicated echo
+            return;
+        };
+        self.pending_probes.swap_remove(index);
+        self.record_probe(true);
+        let rtt = now.saturating_sub(sent_at) as f32;
+        if let Some(previous) = self.rtt_ms {
+            // Same smoothing as RFC 3550
+            self.jitter_ms += ((rtt - previous).abs() - self.jitter_ms) / 16.0;
+        }
+        self.rtt_ms = Some(rtt);
+    }
+}
+
+/// Status of the TCP connection to the server, driven by the socket task
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    Failed(String),
+}
+
+#[derive(Resource)]
+struct ConnectionStateReceiver(Receiver<ConnectionState>);
+
+/// Where the server listens
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ServerAddr {
+    pub tcp: SocketAddr,
+    pub udp: SocketAddr,
+}
+
+impl Default for ServerAddr {
+    fn default() -> Self {
+        Self {
+            tcp: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8080),
+            udp: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8081),
+        }
+    }
+}
+
+impl ServerAddr {
+    /// Overrides addresses from `--server-tcp <addr>` and `--server-udp <addr>`
+    pub fn apply_args(&mut self, args: &[String]) {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let addr = match arg.as_str() {
+                "--server-tcp" => &mut self.tcp,
+                "--server-udp" => &mut self.udp,
+                _ => continue,
+            };
+            match args.next().map(|value| value.parse()) {
+                Some(Ok(value)) => *addr = value,
+                _ => eprintln!("Expected an address such as 127.0.0.1:8080 after {}", arg),
+            }
+        }
+    }
+}
+
+/// What the connections to the server go through, insert it before the
+/// [`NetworkPlugin`] starts to talk to a server over a [`MemoryNetwork`]
+#[derive(Resource, Default, Clone)]
+pub enum Transport {
+    #[default]
+    Sockets,
+    Memory(MemoryNetwork),
+}
+
+/// Channel ends handed to every new TCP connection attempt
+#[derive(Resource, Clone)]
+struct TcpConnector {
+    client_receiver: Receiver<ClientEvent>,
+    server_sender: Sender<ServerEvent>,
+    state_sender: Sender<ConnectionState>,
+    counters: NetCounters,
+    network_simulation: NetSimConfig,
+}
+
+/// Pending automatic reconnection after the server dropped us
+#[derive(Resource, Default)]
+pub struct Reconnect {
+    pub attempt: u32,
+    pub timer: Option<Timer>,
+}
+
+impl Reconnect {
+    fn schedule(&mut self) {
+        self.attempt += 1;
+        let delay = (BASE_RECONNECT_DELAY * 2u32.pow(self.attempt - 1)).min(MAX_RECONNECT_DELAY);
+        info!(
+            "Reconnecting in {:?} (attempt {}/{})",
+            delay, self.attempt, MAX_RECONNECT_ATTEMPTS
+        );
+        self.timer = Some(Timer::new(delay, TimerMode::Once));
+    }
+    fn reset(&mut self) {
+        self.attempt = 0;
+        self.timer = None;
+    }
+    pub fn max_attempts(&self) -> u32 {
+        MAX_RECONNECT_ATTEMPTS
+    }
+}
+
+/// Opens a new TCP connection to the server unless one is already up
+#[derive(Event)]
+pub struct ConnectEvent;
+
 pub struct NetworkPlugin;
 
 impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_tcp)
+        app.init_resource::<ConnectionState>()
+            .init_resource::<Reconnect>()
+            .init_resource::<NetCounters>()
+            .init_resource::<NetStats>()
+            .init_resource::<ServerAddr>()
+            .init_resource::<Transport>()
+            .observe(connect)
+            .add_systems(Startup, setup_tcp)
+            .add_systems(Update, (on_connection_state, tick_reconnect).chain())
             .add_systems(Update, on_tcp_event)
-            .add_systems(Update, on_udp_event.run_if(in_state(GameState::Game)));
+            .add_systems(
+                Update,
+                (on_udp_event, send_udp_probes).run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), reset_net_stats);
+    }
+}
+
+fn connect(
+    _trigger: Trigger<ConnectEvent>,
+    connector: Res<TcpConnector>,
+    transport: Res<Transport>,
+    server_addr: Res<ServerAddr>,
+    mut connection_state: ResMut<ConnectionState>,
+) {
+    if matches!(
+        *connection_state,
+        ConnectionState::Connecting | ConnectionState::Connected
+    ) {
+        return;
+    }
+    *connection_state = ConnectionState::Connecting;
+
+    let connector = connector.clone();
+    let transport = transport.clone();
+    let addr = server_addr.tcp;
+    IoTaskPool::get()
+        .spawn(async move {
+            let state_sender = connector.state_sender.clone();
+            let result = match transport {
+                Transport::Sockets => match transport::async_net::connect(addr).await {
+                    Ok((sender, receiver)) => tcp_socket_task(sender, receiver, connector).await,
+                    Err(e) => Err(e.into()),
+                },
+                Transport::Memory(network) => match network.connect(addr).await {
+                    Ok((sender, receiver)) => tcp_socket_task(sender, receiver, connector).await,
+                    Err(e) => Err(e.into()),
+                },
+            };
+            // Errors can't be held across the await below
+            let state = connection_state_of(result);
+            let _ = state_sender.send(state).await;
+        })
+        .detach();
+}
+
+fn connection_state_of(result: Result<(), Box<dyn std::error::Error>>) -> ConnectionState {
+    match result {
+        Ok(()) => ConnectionState::Disconnected,
+        Err(e) => {
+            error!("Socket task error: {:?}", e);
+            ConnectionState::Failed(e.to_string())
+        }
+    }
+}
+
+fn on_connection_state(
+    state_receiver: Res<ConnectionStateReceiver>,
+    mut connection_state: ResMut<ConnectionState>,
+    mut reconnect: ResMut<Reconnect>,
+    current_game_state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut menu_state: ResMut<NextState<MenuState>>,
+) {
+    while let Ok(new_state) = state_receiver.0.try_recv() {
+        let was_connected = *connection_state == ConnectionState::Connected;
+        match &new_state {
+            ConnectionState::Connected => reconnect.reset(),
+            ConnectionState::Disconnected | ConnectionState::Failed(_) if was_connected => {
+                info!("Lost connection to the server");
+                if *current_game_state.get() == GameState::Game {
+                    game_state.set(GameState::Menu);
+                } else {
+                    menu_state.set(MenuState::Main);
+                }
+                reconnect.schedule();
+            }
+            ConnectionState::Failed(_) if reconnect.attempt > 0 => {
+                if reconnect.attempt < MAX_RECONNECT_ATTEMPTS {
+                    reconnect.schedule();
+                } else {
+                    reconnect.reset();
+                    menu_state.set(MenuState::Error);
+                }
+            }
+            ConnectionState::Failed(_) => {
+                menu_state.set(MenuState::Error);
+            }
+            _ => {}
+        }
+        *connection_state = new_state;
+    }
+}
+
+fn tick_reconnect(time: Res<Time>, mut reconnect: ResMut<Reconnect>, mut commands: Commands) {
+    let Some(timer) = reconnect.timer.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        reconnect.timer = None;
+        commands.trigger(ConnectEvent);
     }
 }
 
 fn on_udp_event(
     socket_receiver: ResMut<UdpSocketReceiver>,
     mut players_query: Query<(&mut Transform, &Player)>,
+    mut stats: ResMut<NetStats>,
+    time: Res<Time<Real>>,
+    mut commands: Commands,
 ) {
+    stats.snapshot_depth = socket_receiver.0.len();
     while let Ok(event) = socket_receiver.0.try_recv() {
         match event {
+            ServerEvent::Pong(ping_event) => {
+                stats.on_pong(ping_event.sent_at, time.elapsed().as_millis() as u64);
+            }
             ServerEvent::PosUpdate(pos_event) => {
                 for (mut transform, player) in players_query.iter_mut() {
                     if player.id == pos_event.client_id {
@@ -48,16 +369,33 @@ fn on_udp_event(
                     }
                 }
             }
+            ServerEvent::Emote(emote_event) => {
+                commands.trigger(EmoteReceivedEvent {
+                    client_id: emote_event.client_id,
+                    emote: emote_event.emote,
+                });
+            }
             ServerEvent::Accept(_) => {}
             ServerEvent::Start(_) => {}
             ServerEvent::Wait => {}
             ServerEvent::Leave(_) => {}
+            ServerEvent::RoundStart(_) => {}
+            ServerEvent::RoundEnd(_) => {}
+            ServerEvent::Tag(_) => {}
+            ServerEvent::Scoreboard(_) => {}
+            ServerEvent::Ping(_) => {}
+            ServerEvent::Chat(_) => {}
         }
     }
 }
+#[allow(clippy::too_many_arguments)]
 fn on_tcp_event(
     socket_receiver: ResMut<TcpSocketReceiver>,
+    tcp_sender: Res<TcpSocketSender>,
     udp_sender: ResMut<UdpSocketSender>,
+    mut arena: ResMut<Arena>,
+    mut round: ResMut<Round>,
+    mut scoreboard: ResMut<Scoreboard>,
     mut game_state: ResMut<NextState<GameState>>,
     mut menu_state: ResMut<NextState<MenuState>>,
     mut commands: Commands,
@@ -74,17 +412,72 @@ fn on_tcp_event(
                 menu_state.set(MenuState::Disabled);
 
                 let player_client_id = start_event.client_id.clone();
+                // Generated maps only come as a seed and get rebuilt locally
+                arena.0 = start_event.map.resolve();
+                commands.trigger(SpawnMapEvent {
+                    map: arena.0.clone(),
+                });
+
+                scoreboard.players = start_event
+                    .players
+                    .iter()
+                    .map(|player| PlayerScore {
+                        client_id: player.id.clone(),
+                        nickname: player.nickname.clone(),
+                        it_count: player.it_count,
+                        ping_ms: None,
+                    })
+                    .collect();
 
                 for player in start_event.players {
                     let is_main = player.id == player_client_id;
                     let player = SpawnPlayerEvent {
                         coords: Vec2::new(player.position.x, player.position.y),
                         id: player.id.clone(),
+                        nickname: player.nickname.clone(),
                         main_player: is_main,
                     };
                     commands.trigger(player);
                 }
             }
+            ServerEvent::RoundStart(round_start) => {
+                round.it = Some(round_start.it);
+                round.timer = Some(Timer::from_seconds(
+                    round_start.duration_secs as f32,
+                    TimerMode::Once,
+                ));
+                commands.trigger(RoundStartEvent);
+            }
+            ServerEvent::RoundEnd(round_end) => {
+                round.it = None;
+                round.timer = None;
+                scoreboard.players = round_end.players;
+                commands.trigger(RoundEndEvent);
+            }
+            ServerEvent::Tag(tag_event) => {
+                round.it = Some(tag_event.it.clone());
+                commands.trigger(PlayerTaggedEvent {
+                    it: tag_event.it,
+                    tagged_by: tag_event.tagged_by,
+                });
+            }
+            ServerEvent::Scoreboard(scoreboard_event) => {
+                scoreboard.players = scoreboard_event.players;
+            }
+            ServerEvent::Ping(ping_event) => {
+                let tcp_sender = tcp_sender.0.clone();
+                task_pool
+                    .spawn(async move {
+                        let _ = tcp_sender.send(ClientEvent::Pong(ping_event)).await;
+                    })
+                    .detach();
+            }
+            ServerEvent::Chat(chat_event) => {
+                commands.trigger(ChatReceivedEvent(chat_event));
+            }
+            ServerEvent::Emote(_) | ServerEvent::Pong(_) => {
+                // Only sent over UDP
+            }
             ServerEvent::Wait => {
                 info!("Waiting for more players...");
             }
@@ -110,9 +503,56 @@ fn on_tcp_event(
     }
 }
 
-pub fn setup_tcp(mut commands: Commands) {
+/// Sends a probe every second and counts the ones that never came back as lost
+fn send_udp_probes(
+    time: Res<Time<Real>>,
+    mut stats: ResMut<NetStats>,
+    udp_sender: Res<UdpSocketSender>,
+) {
+    if !stats.probe_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let now = time.elapsed().as_millis() as u64;
+    let timeout = PROBE_TIMEOUT.as_millis() as u64;
+    let (expired, pending) = stats
+        .pending_probes
+        .iter()
+        .partition::<Vec<u64>, _>(|sent_at| now.saturating_sub(**sent_at) > timeout);
+    stats.pending_probes = pending;
+    for _ in expired {
+        stats.record_probe(false);
+    }
+
+    stats.pending_probes.push(now);
+    let udp_sender = udp_sender.0.clone();
+    IoTaskPool::get()
+        .spawn(async move {
+            let _ = udp_sender
+                .send(ClientEvent::Ping(PingEvent { sent_at: now }))
+                .await;
+        })
+        .detach();
+}
+
+fn reset_net_stats(mut stats: ResMut<NetStats>) {
+    *stats = NetStats::default();
+}
+
+pub fn setup_tcp(
+    mut commands: Commands,
+    counters: Res<NetCounters>,
+    network_simulation: Res<NetSimConfig>,
+    transport: Res<Transport>,
+    server_addr: Res<ServerAddr>,
+) {
+    let network_simulation = *network_simulation;
+    if network_simulation.is_enabled() {
+        info!("Simulating network conditions: {:?}", network_simulation);
+    }
+
     let (tcp_client_sender, tcp_client_receiver) = unbounded::<ClientEvent>();
     let (tcp_server_sender, tcp_server_receiver) = unbounded::<ServerEvent>();
+    let (state_sender, state_receiver) = unbounded::<ConnectionState>();
 
     let (udp_client_sender, udp_client_receiver) = unbounded::<ClientEvent>();
     let (udp_server_sender, udp_server_receiver) = unbounded::<ServerEvent>();
@@ -123,20 +563,55 @@ pub fn setup_tcp(mut commands: Commands) {
     commands.insert_resource(UdpSocketSender(udp_client_sender));
     commands.insert_resource(UdpSocketReceiver(udp_server_receiver));
 
-    let task_pool = IoTaskPool::get();
-    task_pool
-        .spawn(async move {
-            if let Err(e) = tcp_socket_task(tcp_client_receiver, tcp_server_sender, task_pool).await
-            {
-                error!("Socket task error: {:?}", e);
-            }
-        })
-        .detach();
+    commands.insert_resource(ConnectionStateReceiver(state_receiver));
+    commands.insert_resource(TcpConnector {
+        client_receiver: tcp_client_receiver,
+        server_sender: tcp_server_sender,
+        state_sender,
+        counters: counters.clone(),
+        network_simulation,
+    });
+    commands.trigger(ConnectEvent);
 
+    let task_pool = IoTaskPool::get();
+    let counters = counters.0.clone();
+    let transport = transport.clone();
+    let server_addr = server_addr.udp;
+    let any_addr = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0);
     task_pool
         .spawn(async move {
-            if let Err(e) = udp_socket_task(udp_client_receiver, udp_server_sender, task_pool).await
-            {
+            let channels = (udp_client_receiver, udp_server_sender);
+            let result = match transport {
+                Transport::Sockets => match async_net::UdpSocket::bind(any_addr).await {
+                    Ok(socket) => {
+                        udp_socket_task(
+                            socket,
+                            server_addr,
+                            channels,
+                            task_pool,
+                            counters,
+                            network_simulation,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e.into()),
+                },
+                Transport::Memory(network) => match network.bind(any_addr) {
+                    Ok(socket) => {
+                        udp_socket_task(
+                            socket,
+                            server_addr,
+                            channels,
+                            task_pool,
+                            counters,
+                            network_simulation,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e.into()),
+                },
+            };
+            if let Err(e) = result {
                 error!("UDP socket task error: {:?}", e);
             }
         })
@@ -144,35 +619,51 @@ pub fn setup_tcp(mut commands: Commands) {
 }
 
 async fn tcp_socket_task(
-    client_receiver: Receiver<ClientEvent>,
-    server_sender: Sender<ServerEvent>,
-    task_pool: &IoTaskPool,
+    mut sender: impl ReliableSender,
+    mut receiver: impl ReliableReceiver,
+    connector: TcpConnector,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let stream = TcpStream::connect("127.0.0.1:8080").await?;
+    let TcpConnector {
+        client_receiver,
+        server_sender,
+        state_sender,
+        counters,
+        network_simulation,
+    } = connector;
+    let counters = counters.0;
     info!("Connected to server");
+    let _ = state_sender.send(ConnectionState::Connected).await;
 
-    let reader = stream.clone();
-    let writer = stream;
+    // Both directions go through the simulated network, which passes
+    // everything straight through unless it is enabled
+    let (received_sender, received_receiver) = unbounded::<ServerEvent>();
+    let incoming = net_sim::forward_delayed(
+        NetworkConditioner::new(network_simulation),
+        received_receiver,
+        server_sender,
+    );
+    let (outgoing_sender, outgoing_receiver) = unbounded::<ClientEvent>();
+    let outgoing = net_sim::forward_delayed(
+        NetworkConditioner::new(network_simulation),
+        client_receiver,
+        outgoing_sender,
+    );
 
-    let reader = BufReader::new(reader);
-
-    let mut line = String::new();
-
-    let server_sender_clone = server_sender.clone();
+    let read_counters = counters.clone();
     let read_task = async move {
-        let mut reader = reader;
         loop {
-            line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
+            match receiver.recv().await {
+                Ok(None) => {
                     info!("Server closed the connection");
                     break;
                 }
-                Ok(_) => {
+                Ok(Some(line)) => {
+                    // Counting the newline the line came with
+                    read_counters.tcp_received.record(line.len() + 1);
                     let response = line.trim();
                     match serde_json::from_str::<ServerEvent>(response) {
                         Ok(event) => {
-                            let _ = server_sender_clone.send(event).await;
+                            let _ = received_sender.send(event).await;
                         }
                         Err(e) => {
                             error!("Failed to parse server event: {:?}", e);
@@ -186,51 +677,73 @@ async fn tcp_socket_task(
             }
         }
     };
+    // Events already on their way are still delivered after the socket closes
+    let read_task = async move {
+        future::zip(read_task, incoming).await;
+        Ok::<(), Box<dyn std::error::Error>>(())
+    };
 
     let write_task = async move {
-        let mut writer = writer;
-        while let Ok(event) = client_receiver.recv().await {
+        while let Ok(event) = outgoing_receiver.recv().await {
             info!("Receiver sending event over TCP...");
             let msg = serde_json::to_string(&event)? + "\n";
-            writer.write_all(msg.as_bytes()).await?;
-            writer.flush().await?;
+            sender.send(&msg).await?;
+            counters.tcp_sent.record(msg.len());
         }
         Ok::<(), Box<dyn std::error::Error>>(())
     };
+    let outgoing = async move {
+        outgoing.await;
+        future::pending().await
+    };
 
-    task_pool.spawn(read_task).detach();
-    write_task.await?;
+    // Whichever half finishes first tears down the connection so the
+    // writer stops draining events meant for the next connection
+    read_task.or(write_task).or(outgoing).await?;
 
     Ok(())
 }
 
 async fn udp_socket_task(
-    client_receiver: Receiver<ClientEvent>,
-    server_sender: Sender<ServerEvent>,
+    socket: impl UnreliableSocket,
+    server_addr: SocketAddr,
+    (client_receiver, server_sender): (Receiver<ClientEvent>, Sender<ServerEvent>),
     task_pool: &IoTaskPool,
+    counters: Arc<TrafficCounters>,
+    network_simulation: NetSimConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let socket = Arc::new(UdpSocket::bind("0.0.0.0:0").await?);
+    let socket = Arc::new(socket);
     info!("UDP socket bound to {}", socket.local_addr()?);
-
-    socket.connect("127.0.0.1:8081").await?;
-    info!("Connected to UDP server at 127.0.0.1:8081");
+    info!("Sending UDP to server at {}", server_addr);
 
     let socket_clone = socket.clone();
 
-    let server_sender_clone = server_sender.clone();
+    let read_counters = counters.clone();
+    let mut incoming = NetworkConditioner::new(network_simulation);
     let read_task = async move {
         let mut buf = [0u8; 1024];
         loop {
-            match socket_clone.recv(&mut buf).await {
-                Ok(len) => {
-                    let msg = String::from_utf8_lossy(&buf[..len]);
-                    match serde_json::from_str::<ServerEvent>(&msg) {
-                        Ok(event) => {
-                            let _ = server_sender_clone.send(event).await;
-                        }
-                        Err(e) => {
-                            error!("Failed to parse UDP server event: {:?}", e);
-                        }
+            match socket_clone.recv_from(&mut buf).await {
+                // Anything else is not from the server
+                Ok((_, from)) if from != server_addr => {}
+                Ok((len, _)) => {
+                    read_counters.udp_received.record(len);
+                    let msg = String::from_utf8_lossy(&buf[..len]).into_owned();
+                    // Parsed once per copy since server events can't be cloned
+                    for delay in incoming.unreliable() {
+                        let msg = msg.clone();
+                        let server_sender = server_sender.clone();
+                        net_sim::run_after(delay, async move {
+                            match serde_json::from_str::<ServerEvent>(&msg) {
+                                Ok(event) => {
+                                    let _ = server_sender.send(event).await;
+                                }
+                                Err(e) => {
+                                    error!("Failed to parse UDP server event: {:?}", e);
+                                }
+                            }
+                        })
+                        .await;
                     }
                 }
                 Err(e) => {
@@ -241,10 +754,22 @@ async fn udp_socket_task(
         }
     };
 
+    let mut outgoing = NetworkConditioner::new(network_simulation);
     let write_task = async move {
         while let Ok(event) = client_receiver.recv().await {
             let msg = serde_json::to_string(&event)? + "\n";
-            socket.send(msg.as_bytes()).await?;
+            for delay in outgoing.unreliable() {
+                let socket = socket.clone();
+                let counters = counters.clone();
+                let msg = msg.clone();
+                net_sim::run_after(delay, async move {
+                    match socket.send_to(msg.as_bytes(), server_addr).await {
+                        Ok(()) => counters.udp_sent.record(msg.len()),
+                        Err(e) => error!("Failed to send UDP message: {:?}", e),
+                    }
+                })
+                .await;
+            }
         }
         Ok::<(), Box<dyn std::error::Error>>(())
     };
@@ -0,0 +1,180 @@
+use crate::net::{ChannelCounter, NetCounters, NetStats};
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// Interval the packet and byte rates are averaged over
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct NetDebugPlugin;
+
+impl Plugin for NetDebugPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        app.init_resource::<TrafficRates>()
+            .add_systems(Startup, setup_overlay)
+            .add_systems(
+                Update,
+                (
+                    toggle_overlay,
+                    sample_rates,
+                    update_overlay.run_if(overlay_visible),
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct NetDebugOverlay;
+
+/// Per second rates of one direction of a socket
+#[derive(Default, Clone, Copy)]
+struct Rate {
+    packets: f64,
+    bytes: f64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Totals {
+    packets: u64,
+    bytes: u64,
+}
+
+impl Totals {
+    fn of(counter: &ChannelCounter) -> Self {
+        Self {
+            packets: counter.packets(),
+            bytes: counter.bytes(),
+        }
+    }
+    fn rate_since(self, previous: Totals, secs: f64) -> Rate {
+        Rate {
+            packets: (self.packets - previous.packets) as f64 / secs,
+            bytes: (self.bytes - previous.bytes) as f64 / secs,
+        }
+    }
+}
+
+/// TCP sent, TCP received, UDP sent and UDP received, in that order
+#[derive(Resource, Default)]
+struct TrafficRates {
+    elapsed: Duration,
+    last_totals: [Totals; 4],
+    rates: [Rate; 4],
+}
+
+fn setup_overlay(mut commands: Commands) {
+    commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            })
+            .with_background_color(Color::BLACK.with_alpha(0.6)),
+            NetDebugOverlay,
+        ))
+        .insert((Visibility::Hidden, ZIndex::Global(20)));
+}
+
+fn overlay_visible(query: Query<&Visibility, With<NetDebugOverlay>>) -> bool {
+    query
+        .iter()
+        .any(|visibility| visibility == Visibility::Visible)
+}
+
+fn toggle_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut query: Query<&mut Visibility, With<NetDebugOverlay>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut visibility in query.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Visible => Visibility::Hidden,
+            _ => Visibility::Visible,
+        };
+    }
+}
+
+fn sample_rates(
+    time: Res<Time<Real>>,
+    counters: Res<NetCounters>,
+    mut rates: ResMut<TrafficRates>,
+) {
+    rates.elapsed += time.delta();
+    if rates.elapsed < RATE_INTERVAL {
+        return;
+    }
+    let secs = rates.elapsed.as_secs_f64();
+    let totals = [
+        Totals::of(&counters.0.tcp_sent),
+        Totals::of(&counters.0.tcp_received),
+        Totals::of(&counters.0.udp_sent),
+        Totals::of(&counters.0.udp_received),
+    ];
+    for (i, total) in totals.iter().enumerate() {
+        rates.rates[i] = total.rate_since(rates.last_totals[i], secs);
+    }
+    rates.last_totals = totals;
+    rates.elapsed = Duration::ZERO;
+}
+
+fn format_rate(rate: Rate) -> String {
+    let bytes = if rate.bytes >= 1024.0 {
+        format!("{:.1} KB/s", rate.bytes / 1024.0)
+    } else {
+        format!("{:.0} B/s", rate.bytes)
+    };
+    format!("{:>4.0} pkt/s {:>9}", rate.packets, bytes)
+}
+
+fn update_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    stats: Res<NetStats>,
+    rates: Res<TrafficRates>,
+    mut query: Query<&mut Text, With<NetDebugOverlay>>,
+) {
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed())
+        .map_or("-".to_string(), |fps| format!("{:.0}", fps));
+    let rtt = stats
+        .rtt_ms
+        .map_or("-".to_string(), |rtt| format!("{:.0} ms", rtt));
+    let loss = stats
+        .packet_loss()
+        .map_or("-".to_string(), |loss| format!("{:.1}%", loss * 100.0));
+    let [tcp_up, tcp_down, udp_up, udp_down] = rates.rates;
+
+    let content = [
+        format!("FPS: {}", fps),
+        format!(
+            "RTT: {}  jitter: {:.1} ms  loss: {}",
+            rtt, stats.jitter_ms, loss
+        ),
+        format!("TCP up   {}", format_rate(tcp_up)),
+        format!("TCP down {}", format_rate(tcp_down)),
+        format!("UDP up   {}", format_rate(udp_up)),
+        format!("UDP down {}", format_rate(udp_down)),
+        format!("Snapshot buffer: {}", stats.snapshot_depth),
+    ]
+    .join("\n");
+    for mut text in query.iter_mut() {
+        text.sections[0].value.clone_from(&content);
+    }
+}
@@ -0,0 +1,177 @@
+use async_channel::{unbounded, Receiver, Sender};
+use async_io::Timer;
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::future;
+use bevy::tasks::IoTaskPool;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Network conditions simulated by the socket tasks, all zero disables the simulation
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetSimConfig {
+    /// Added one way, in both directions
+    pub latency_ms: u32,
+    /// Random extra delay, between 0 and this
+    pub jitter_ms: u32,
+    /// Chance between 0 and 1 for a UDP packet to be dropped
+    pub loss: f32,
+    /// Chance between 0 and 1 for a UDP packet to arrive twice
+    pub duplicate: f32,
+    /// Chance between 0 and 1 for a UDP packet to be held back behind later ones
+    pub reorder: f32,
+}
+
+impl NetSimConfig {
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Overrides values from `--sim-latency <ms>`, `--sim-jitter <ms>`, `--sim-loss <0-1>`,
+    /// `--sim-duplicate <0-1>` and `--sim-reorder <0-1>`
+    pub fn apply_args(&mut self, args: &[String]) {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--sim-") else {
+                continue;
+            };
+            let Some(value) = args.clone().next() else {
+                eprintln!("Expected a value after {}", arg);
+                continue;
+            };
+            let parsed = match name {
+                "latency" => value.parse().map(|v| self.latency_ms = v).is_ok(),
+                "jitter" => value.parse().map(|v| self.jitter_ms = v).is_ok(),
+                "loss" => value.parse().map(|v| self.loss = v).is_ok(),
+                "duplicate" => value.parse().map(|v| self.duplicate = v).is_ok(),
+                "reorder" => value.parse().map(|v| self.reorder = v).is_ok(),
+                _ => {
+                    eprintln!("Unknown network simulation flag {}", arg);
+                    continue;
+                }
+            };
+            if parsed {
+                args.next();
+            } else {
+                eprintln!("Invalid value {} for {}", value, arg);
+            }
+        }
+    }
+}
+
+/// Decides when packets going one way get delivered
+pub struct NetworkConditioner {
+    config: NetSimConfig,
+    rng: fastrand::Rng,
+    /// Delivery time of the last packet of an ordered stream
+    last_delivery: Option<Instant>,
+}
+
+impl NetworkConditioner {
+    pub fn new(config: NetSimConfig) -> Self {
+        Self {
+            config,
+            rng: fastrand::Rng::new(),
+            last_delivery: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    fn delay(&mut self) -> Duration {
+        let jitter = if self.config.jitter_ms > 0 {
+            self.rng.u32(0..=self.config.jitter_ms)
+        } else {
+            0
+        };
+        Duration::from_millis((self.config.latency_ms + jitter) as u64)
+    }
+
+    /// Delays for each copy of an unreliable packet, empty when it gets lost
+    pub fn unreliable(&mut self) -> Vec<Duration> {
+        if self.rng.f32() < self.config.loss {
+            return Vec::new();
+        }
+        let copies = if self.rng.f32() < self.config.duplicate {
+            2
+        } else {
+            1
+        };
+        (0..copies)
+            .map(|_| {
+                let delay = self.delay();
+                if self.rng.f32() < self.config.reorder {
+                    // Long enough for the next packets to overtake it
+                    delay + Duration::from_millis(self.config.jitter_ms.max(50) as u64)
+                } else {
+                    delay
+                }
+            })
+            .collect()
+    }
+
+    /// When a packet of an ordered stream should be delivered, never before the previous one
+    pub fn reliable(&mut self) -> Instant {
+        let mut deliver_at = Instant::now() + self.delay();
+        if let Some(last_delivery) = self.last_delivery {
+            deliver_at = deliver_at.max(last_delivery);
+        }
+        self.last_delivery = Some(deliver_at);
+        deliver_at
+    }
+}
+
+/// Runs `task` after `delay`, in place when there is no delay so order is kept
+pub async fn run_after(delay: Duration, task: impl Future<Output = ()> + Send + 'static) {
+    if delay.is_zero() {
+        task.await;
+    } else {
+        IoTaskPool::get()
+            .spawn(async move {
+                Timer::after(delay).await;
+                task.await;
+            })
+            .detach();
+    }
+}
+
+/// Forwards everything from `input` to `output` with the simulated latency, keeping the order
+pub async fn forward_delayed<T: Send + 'static>(
+    mut conditioner: NetworkConditioner,
+    input: Receiver<T>,
+    output: Sender<T>,
+) {
+    if !conditioner.is_enabled() {
+        while let Ok(item) = input.recv().await {
+            if output.send(item).await.is_err() {
+                break;
+            }
+        }
+        return;
+    }
+    let (stamped_sender, stamped_receiver) = unbounded::<(Instant, T)>();
+    // Items are stamped as soon as they come in so waiting on one does not delay the next
+    let stamp = async move {
+        while let Ok(item) = input.recv().await {
+            if stamped_sender
+                .send((conditioner.reliable(), item))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    };
+    let deliver = async move {
+        while let Ok((deliver_at, item)) = stamped_receiver.recv().await {
+            Timer::at(deliver_at).await;
+            if output.send(item).await.is_err() {
+                break;
+            }
+        }
+    };
+    future::zip(stamp, deliver).await;
+}
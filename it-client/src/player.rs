@@ -1,14 +1,35 @@
+use crate::animation::{
+    AnimationSheet, FacingDirection, PlayerAnimationSheet, RemoteMotion, SpriteAnimation,
+};
+use crate::game::Arena;
+use crate::input::ActionState;
+use crate::menu::MenuState;
 use crate::net::UdpSocketSender;
-use crate::GameState;
+use crate::{cleanup_entities, GameState};
 use bevy::prelude::*;
 use bevy::tasks::IoTaskPool;
-use bevy::window::PrimaryWindow;
 use bevy_rapier2d::prelude::*;
-use it_core::PosUpdateEvent;
+use it_core::{InputState, PosUpdateEvent, Position};
+
+const SPEED: f32 = 150.0;
+const DASH_SPEED: f32 = 400.0;
+const DASH_DURATION: f32 = 0.15;
+const DASH_COOLDOWN: f32 = 1.5;
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(PlayerMovementPlugin)
+            .observe(add_player_sprite);
+    }
+}
+
+/// Player bodies and the main player's movement, without anything rendering
+/// them so the headless client plays by the same rules
+pub struct PlayerMovementPlugin;
+
+impl Plugin for PlayerMovementPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CurrentPlayerPos>()
             .observe(spawn_player)
@@ -20,7 +41,12 @@ impl Plugin for PlayerPlugin {
                     broadcast_main_player_pos,
                 )
                     .run_if(in_state(GameState::Game)),
-            );
+            )
+            .add_systems(
+                OnExit(MenuState::Disabled),
+                stop_main_player.run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), cleanup_entities::<Player>);
     }
 }
 
@@ -28,164 +54,208 @@ impl Plugin for PlayerPlugin {
 pub struct SpawnPlayerEvent {
     pub coords: Vec2,
     pub id: String,
+    pub nickname: String,
     pub main_player: bool,
 }
 
 #[derive(Component)]
 pub struct Player {
     pub id: String,
+    pub nickname: String,
+}
+
+#[derive(Component)]
+pub struct MainPlayer;
+
+/// Input applied to the main player this frame, empty while a menu is open
+#[derive(Component, Default, PartialEq)]
+pub struct PlayerInput(pub InputState);
+
+#[derive(Component)]
+struct Dash {
+    active: Timer,
+    cooldown: Timer,
 }
 
+impl Default for Dash {
+    fn default() -> Self {
+        let mut active = Timer::from_seconds(DASH_DURATION, TimerMode::Once);
+        let mut cooldown = Timer::from_seconds(DASH_COOLDOWN, TimerMode::Once);
+        active.tick(active.duration());
+        cooldown.tick(cooldown.duration());
+        Self { active, cooldown }
+    }
+}
+
+/// Nickname label floating above a player
 #[derive(Component)]
-struct MainPlayer;
+pub struct PlayerLabel;
 
 #[derive(Bundle)]
 struct PlayerBundle {
     name: Name,
     player: Player,
-    sprite: SpriteBundle,
+    transform: TransformBundle,
     rigid_body: RigidBody,
     locked_axes: LockedAxes,
     collider: Collider,
     velocity: Velocity,
-    texture: TextureAtlas,
 }
 
 #[derive(Resource, Default)]
 pub struct CurrentPlayerPos {
     pub position: Option<Vec3>,
+    pub input: InputState,
 }
 
 fn spawn_player(
     trigger: Trigger<SpawnPlayerEvent>,
     mut commands: Commands,
-    mut textures: ResMut<Assets<TextureAtlasLayout>>,
-    asset_server: Res<AssetServer>,
     mut rapier_config: ResMut<RapierConfiguration>,
 ) {
     rapier_config.gravity = Vec2::ZERO;
-    let texture_handle = asset_server.load("slime.png");
-
-    let layout = TextureAtlasLayout::from_grid(UVec2::new(32, 32), 7, 13, None, None);
-    let texture_atlas_handle = textures.add(layout);
-
-    let coords = trigger.event().coords;
-    let coords = Vec3::new(coords.x, coords.y, 0.0);
-
-    let player_name = format!("Player-{}", trigger.event().id);
-
+    let event = trigger.event();
     let player_bundle = PlayerBundle {
-        name: Name::new(player_name.clone()),
+        name: Name::new(format!("Player-{}", event.id)),
         player: Player {
-            id: trigger.event().id.clone(),
+            id: event.id.clone(),
+            nickname: event.nickname.clone(),
         },
-        texture: TextureAtlas {
-            index: 0,
-            layout: texture_atlas_handle,
-        },
-        sprite: SpriteBundle {
-            texture: texture_handle,
-            transform: Transform {
-                translation: coords,
-                scale: Vec3::splat(1.5),
-                ..default()
-            },
+        transform: TransformBundle::from_transform(Transform {
+            translation: event.coords.extend(0.0),
+            scale: Vec3::splat(1.5),
             ..default()
-        },
+        }),
         locked_axes: LockedAxes::ROTATION_LOCKED,
         rigid_body: RigidBody::Dynamic,
         collider: Collider::cuboid(8.0, 8.0),
         velocity: Velocity::default(),
     };
-    let entity = commands.spawn(player_bundle).id();
-    if trigger.event().main_player {
-        commands.entity(entity).insert(MainPlayer);
+    // Spawned in one go so the sprite observer sees whether it is the main player
+    if event.main_player {
+        commands.spawn((
+            player_bundle,
+            MainPlayer,
+            PlayerInput::default(),
+            Dash::default(),
+        ));
+    } else {
+        commands.spawn(player_bundle);
     }
-    commands.entity(entity).with_children(|p| {
-        p.spawn(Text2dBundle {
-            text: Text::from_section(
-                player_name,
-                TextStyle {
-                    font_size: 11.0,
-                    color: Color::WHITE,
-                    ..default()
-                },
-            ),
-            transform: Transform::from_xyz(0.0, 15.0, 0.0),
-            ..default()
-        });
-    });
 }
-fn wrap_player_position(
-    mut query: Query<&mut Transform, With<Player>>,
-    windows: Query<&Window, With<PrimaryWindow>>,
-) {
-    let window = windows.single();
-    let half_width = window.width() / 2.0;
-    let half_height = window.height() / 2.0;
 
-    for mut transform in query.iter_mut() {
-        let mut position = transform.translation;
+/// Gives players spawned by [`PlayerMovementPlugin`] their sprite and nickname label
+fn add_player_sprite(
+    trigger: Trigger<OnAdd, Player>,
+    query: Query<(&Player, Has<MainPlayer>)>,
+    mut commands: Commands,
+    sheets: Res<Assets<AnimationSheet>>,
+    player_sheet: Res<PlayerAnimationSheet>,
+) {
+    let Ok((player, main_player)) = query.get(trigger.entity()) else {
+        return;
+    };
+    // Texture and layout get filled in once the sheet finishes loading
+    let (texture_handle, texture_atlas_handle) = sheets
+        .get(&player_sheet.0)
+        .map(|sheet| (sheet.texture.clone(), sheet.layout.clone()))
+        .unwrap_or_default();
 
-        if position.x > half_width {
-            position.x = -half_width;
-        } else if position.x < -half_width {
-            position.x = half_width;
-        }
+    let mut entity = commands.entity(trigger.entity());
+    entity.insert((
+        Sprite::default(),
+        texture_handle,
+        TextureAtlas {
+            index: 0,
+            layout: texture_atlas_handle,
+        },
+        VisibilityBundle::default(),
+        FacingDirection::default(),
+        SpriteAnimation::new(player_sheet.0.clone()),
+    ));
+    if !main_player {
+        entity.insert(RemoteMotion::default());
+    }
+    entity.with_children(|p| {
+        p.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    player.nickname.clone(),
+                    TextStyle {
+                        font_size: 11.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_xyz(0.0, 15.0, 0.0),
+                ..default()
+            },
+            PlayerLabel,
+        ));
+    });
+}
 
-        if position.y > half_height {
-            position.y = -half_height;
-        } else if position.y < -half_height {
-            position.y = half_height;
-        }
+fn wrap_player_position(mut query: Query<&mut Transform, With<Player>>, arena: Res<Arena>) {
+    for mut transform in query.iter_mut() {
+        let position = arena.0.bounds.wrap(&Position {
+            x: transform.translation.x,
+            y: transform.translation.y,
+        });
 
-        transform.translation = position;
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
     }
 }
 
 fn main_player_inputs(
-    mut query: Query<&mut Velocity, With<MainPlayer>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut query: Query<(&mut Velocity, &mut PlayerInput, &mut Dash), With<MainPlayer>>,
+    actions: Res<ActionState>,
+    menu_state: Res<State<MenuState>>,
+    time: Res<Time>,
 ) {
-    let mut velocity = query.single_mut();
+    let (mut velocity, mut input, mut dash) = query.single_mut();
 
-    let speed = 150.0;
+    // Menus such as pause take the input while open
+    if *menu_state.get() != MenuState::Disabled {
+        return;
+    }
+    input.set_if_neq(PlayerInput(actions.snapshot()));
 
-    let mut direction = Vec2::ZERO;
+    dash.active.tick(time.delta());
+    dash.cooldown.tick(time.delta());
 
-    if keyboard_input.pressed(KeyCode::KeyW) || keyboard_input.pressed(KeyCode::ArrowUp) {
-        direction.y += 1.0;
-    }
-    if keyboard_input.pressed(KeyCode::KeyS) || keyboard_input.pressed(KeyCode::ArrowDown) {
-        direction.y -= 1.0;
-    }
-    if keyboard_input.pressed(KeyCode::KeyA) || keyboard_input.pressed(KeyCode::ArrowLeft) {
-        direction.x -= 1.0;
-    }
-    if keyboard_input.pressed(KeyCode::KeyD) || keyboard_input.pressed(KeyCode::ArrowRight) {
-        direction.x += 1.0;
+    let direction = actions.move_axis();
+    if input.0.dash && dash.cooldown.finished() && direction != Vec2::ZERO {
+        dash.active.reset();
+        dash.cooldown.reset();
     }
 
-    if direction.length_squared() > 0.0 {
-        direction = direction.normalize();
-        velocity.linvel = direction * speed;
+    // Analog sticks move slower when only partially tilted
+    if !dash.active.finished() {
+        velocity.linvel = direction.normalize_or_zero() * DASH_SPEED;
     } else {
+        velocity.linvel = direction * SPEED;
+    }
+}
+
+fn stop_main_player(mut query: Query<(&mut Velocity, &mut PlayerInput), With<MainPlayer>>) {
+    for (mut velocity, mut input) in query.iter_mut() {
         velocity.linvel = Vec2::ZERO;
+        input.0 = InputState::default();
     }
 }
 
 fn broadcast_main_player_pos(
     mut last_pos: ResMut<CurrentPlayerPos>,
     socket_sender: ResMut<UdpSocketSender>,
-    player_q: Query<(&Transform, &Player), With<MainPlayer>>,
+    player_q: Query<(&Transform, &Player, &PlayerInput), With<MainPlayer>>,
 ) {
-    let (transform, player) = player_q.single();
+    let (transform, player, input) = player_q.single();
     let coords = transform.translation;
+    let input = input.0;
 
-    if let Some(last_pos) = last_pos.position {
-        if coords == last_pos {
-            return;
-        }
+    if last_pos.position == Some(coords) && last_pos.input == input {
+        return;
     }
 
     let task_pool = IoTaskPool::get();
@@ -198,9 +268,11 @@ fn broadcast_main_player_pos(
                     x: coords.x,
                     y: coords.y,
                     client_id: player_id,
+                    input,
                 }))
                 .await;
         })
         .detach();
     last_pos.position = Some(coords);
+    last_pos.input = input;
 }
@@ -0,0 +1,111 @@
+use crate::game::Arena;
+use crate::player::{MainPlayer, Player, PlayerInput};
+use crate::GameState;
+use bevy::prelude::*;
+use it_core::{InputState, Map};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const REPLAYS_DIR: &str = "replays";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputRecorder>()
+            .init_resource::<RecordReplays>()
+            .add_systems(OnEnter(GameState::Game), start_recording)
+            .add_systems(
+                Update,
+                record_input.run_if(in_state(GameState::Game).and_then(recording_enabled)),
+            )
+            .add_systems(OnExit(GameState::Game), save_replay);
+    }
+}
+
+/// Whether games are recorded to [`REPLAYS_DIR`], off unless asked for
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct RecordReplays(pub bool);
+
+impl RecordReplays {
+    /// Turns recording on with `--record`
+    pub fn apply_args(&mut self, args: &[String]) {
+        if args.iter().any(|arg| arg == "--record") {
+            self.0 = true;
+        }
+    }
+}
+
+fn recording_enabled(record: Res<RecordReplays>) -> bool {
+    record.0
+}
+
+/// Inputs of the main player for one game, saved as JSON when the game ends
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Replay {
+    pub client_id: String,
+    pub map: Map,
+    pub frames: Vec<ReplayFrame>,
+}
+
+/// Input that started at `time_ms` into the game and held until the next frame
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub time_ms: u64,
+    pub input: InputState,
+}
+
+#[derive(Resource, Default)]
+struct InputRecorder {
+    started: f64,
+    replay: Replay,
+}
+
+fn start_recording(mut recorder: ResMut<InputRecorder>, time: Res<Time>) {
+    recorder.started = time.elapsed_seconds_f64();
+    recorder.replay = Replay::default();
+}
+
+fn record_input(
+    mut recorder: ResMut<InputRecorder>,
+    time: Res<Time>,
+    arena: Res<Arena>,
+    query: Query<(&Player, &PlayerInput), With<MainPlayer>>,
+) {
+    let Ok((player, input)) = query.get_single() else {
+        return;
+    };
+    if recorder.replay.client_id.is_empty() {
+        recorder.replay.client_id.clone_from(&player.id);
+        recorder.replay.map = arena.0.clone();
+    }
+    if recorder.replay.frames.last().map(|frame| frame.input) == Some(input.0) {
+        return;
+    }
+    let time_ms = ((time.elapsed_seconds_f64() - recorder.started) * 1000.0) as u64;
+    recorder.replay.frames.push(ReplayFrame {
+        time_ms,
+        input: input.0,
+    });
+}
+
+fn save_replay(mut recorder: ResMut<InputRecorder>) {
+    let replay = std::mem::take(&mut recorder.replay);
+    // Also empty when recording is off
+    if replay.frames.is_empty() {
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = Path::new(REPLAYS_DIR).join(format!("{}.json", timestamp));
+    let result = std::fs::create_dir_all(REPLAYS_DIR).and_then(|_| {
+        let content = serde_json::to_string(&replay).map_err(std::io::Error::other)?;
+        std::fs::write(&path, content)
+    });
+    match result {
+        Ok(()) => info!("Replay saved to {}", path.display()),
+        Err(e) => error!("Failed to save replay to {}: {}", path.display(), e),
+    }
+}
@@ -0,0 +1,311 @@
+use crate::game::minimap::MinimapCorner;
+use crate::net_sim::NetSimConfig;
+use bevy::prelude::*;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use bevy::winit::{UpdateMode, WinitSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>().add_systems(
+            Update,
+            (apply_display_settings, apply_fps_cap).run_if(resource_changed::<Settings>),
+        );
+    }
+}
+
+/// Actions that can be bound to keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum KeyAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Dash,
+    Emote1,
+    Emote2,
+    Emote3,
+    Emote4,
+    Scoreboard,
+    Minimap,
+    CameraMode,
+    Pause,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 13] = [
+        KeyAction::MoveUp,
+        KeyAction::MoveDown,
+        KeyAction::MoveLeft,
+        KeyAction::MoveRight,
+        KeyAction::Dash,
+        KeyAction::Emote1,
+        KeyAction::Emote2,
+        KeyAction::Emote3,
+        KeyAction::Emote4,
+        KeyAction::Scoreboard,
+        KeyAction::Minimap,
+        KeyAction::CameraMode,
+        KeyAction::Pause,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyAction::MoveUp => "Move up",
+            KeyAction::MoveDown => "Move down",
+            KeyAction::MoveLeft => "Move left",
+            KeyAction::MoveRight => "Move right",
+            KeyAction::Dash => "Dash",
+            KeyAction::Emote1 => "Emote 1",
+            KeyAction::Emote2 => "Emote 2",
+            KeyAction::Emote3 => "Emote 3",
+            KeyAction::Emote4 => "Emote 4",
+            KeyAction::Scoreboard => "Scoreboard",
+            KeyAction::Minimap => "Minimap",
+            KeyAction::CameraMode => "Camera mode",
+            KeyAction::Pause => "Pause",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub primary: Option<KeyCode>,
+    pub secondary: Option<KeyCode>,
+}
+
+impl KeyBinding {
+    fn new(primary: KeyCode, secondary: Option<KeyCode>) -> Self {
+        Self {
+            primary: Some(primary),
+            secondary,
+        }
+    }
+    fn keys(&self) -> impl Iterator<Item = KeyCode> {
+        self.primary.into_iter().chain(self.secondary)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowModeSetting {
+    #[default]
+    Windowed,
+    BorderlessFullscreen,
+    Fullscreen,
+}
+
+impl WindowModeSetting {
+    pub fn next(self) -> Self {
+        match self {
+            WindowModeSetting::Windowed => WindowModeSetting::BorderlessFullscreen,
+            WindowModeSetting::BorderlessFullscreen => WindowModeSetting::Fullscreen,
+            WindowModeSetting::Fullscreen => WindowModeSetting::Windowed,
+        }
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            WindowModeSetting::Windowed => "Windowed",
+            WindowModeSetting::BorderlessFullscreen => "Borderless",
+            WindowModeSetting::Fullscreen => "Fullscreen",
+        }
+    }
+}
+
+impl From<WindowModeSetting> for WindowMode {
+    fn from(mode: WindowModeSetting) -> Self {
+        match mode {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::BorderlessFullscreen => WindowMode::BorderlessFullscreen,
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+/// FPS caps cycled through in the settings menu, `None` is unlimited
+pub const FPS_CAPS: [Option<u32>; 6] = [None, Some(30), Some(60), Some(120), Some(144), Some(240)];
+
+/// User settings, persisted as JSON in the user's config directory
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub key_bindings: BTreeMap<KeyAction, KeyBinding>,
+    pub vsync: bool,
+    pub window_mode: WindowModeSetting,
+    pub fps_cap: Option<u32>,
+    /// Radial deadzone applied to the gamepad sticks
+    pub gamepad_deadzone: f32,
+    /// Volumes between 0 and 1
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    pub minimap_corner: MinimapCorner,
+    /// Simulated latency and packet loss, overridden by the `--sim-*` flags
+    pub network_simulation: NetSimConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let key_bindings = BTreeMap::from([
+            (
+                KeyAction::MoveUp,
+                KeyBinding::new(KeyCode::KeyW, Some(KeyCode::ArrowUp)),
+            ),
+            (
+                KeyAction::MoveDown,
+                KeyBinding::new(KeyCode::KeyS, Some(KeyCode::ArrowDown)),
+            ),
+            (
+                KeyAction::MoveLeft,
+                KeyBinding::new(KeyCode::KeyA, Some(KeyCode::ArrowLeft)),
+            ),
+            (
+                KeyAction::MoveRight,
+                KeyBinding::new(KeyCode::KeyD, Some(KeyCode::ArrowRight)),
+            ),
+            (
+                KeyAction::Dash,
+                KeyBinding::new(KeyCode::Space, Some(KeyCode::ShiftLeft)),
+            ),
+            (KeyAction::Emote1, KeyBinding::new(KeyCode::Digit1, None)),
+            (KeyAction::Emote2, KeyBinding::new(KeyCode::Digit2, None)),
+            (KeyAction::Emote3, KeyBinding::new(KeyCode::Digit3, None)),
+            (KeyAction::Emote4, KeyBinding::new(KeyCode::Digit4, None)),
+            (KeyAction::Scoreboard, KeyBinding::new(KeyCode::Tab, None)),
+            (KeyAction::Minimap, KeyBinding::new(KeyCode::KeyM, None)),
+            (KeyAction::CameraMode, KeyBinding::new(KeyCode::KeyV, None)),
+            (KeyAction::Pause, KeyBinding::new(KeyCode::Escape, None)),
+        ]);
+        Self {
+            key_bindings,
+            vsync: false,
+            window_mode: WindowModeSetting::Windowed,
+            fps_cap: None,
+            gamepad_deadzone: 0.15,
+            master_volume: 1.0,
+            music_volume: 0.5,
+            effects_volume: 0.8,
+            minimap_corner: MinimapCorner::default(),
+            network_simulation: NetSimConfig::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("it").join("settings.json"))
+    }
+    /// Loads the saved settings, falling back to the defaults
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error!("Invalid settings file {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let content = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+                std::fs::write(&path, content)
+            });
+        match result {
+            Ok(()) => info!("Settings saved to {}", path.display()),
+            Err(e) => error!("Failed to save settings to {}: {}", path.display(), e),
+        }
+    }
+    pub fn binding(&self, action: KeyAction) -> KeyBinding {
+        self.key_bindings
+            .get(&action)
+            .copied()
+            .or_else(|| Settings::default().key_bindings.get(&action).copied())
+            .unwrap_or(KeyBinding {
+                primary: None,
+                secondary: None,
+            })
+    }
+    pub fn pressed(&self, action: KeyAction, keyboard_input: &ButtonInput<KeyCode>) -> bool {
+        keyboard_input.any_pressed(self.binding(action).keys())
+    }
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+}
+
+/// Platform config directory, e.g. `~/.config` on Linux
+fn config_dir() -> Option<PathBuf> {
+    let env_dir = |name| {
+        std::env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(target_os = "windows") {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    }
+}
+
+/// Short name of a key for menus, e.g. `W` for `KeyW`
+pub fn key_label(key: Option<KeyCode>) -> String {
+    match key {
+        Some(key) => {
+            let name = format!("{:?}", key);
+            name.strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Digit"))
+                .unwrap_or(&name)
+                .to_string()
+        }
+        None => "-".to_string(),
+    }
+}
+
+fn apply_display_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    for mut window in windows.iter_mut() {
+        window.present_mode = settings.present_mode();
+        window.mode = settings.window_mode.into();
+    }
+}
+
+/// Has winit wait out the rest of the frame under the FPS cap, ignoring input
+/// events so moving the mouse doesn't draw extra frames
+fn apply_fps_cap(settings: Res<Settings>, mut winit_settings: ResMut<WinitSettings>) {
+    *winit_settings = match settings.fps_cap {
+        Some(fps_cap) => {
+            let capped = UpdateMode::Reactive {
+                wait: Duration::from_secs_f64(1.0 / fps_cap.max(1) as f64),
+                react_to_device_events: false,
+                react_to_user_events: false,
+                react_to_window_events: false,
+            };
+            WinitSettings {
+                focused_mode: capped,
+                unfocused_mode: capped,
+            }
+        }
+        None => WinitSettings::game(),
+    };
+}
@@ -0,0 +1,99 @@
+use bevy::app::AppExit;
+use it_client::headless::{self, HeadlessConfig};
+use it_client::net::{ServerAddr, Transport};
+use it_client::net_sim::NetSimConfig;
+use it_core::transport::MemoryNetwork;
+use it_core::ServerEvent;
+use it_server::test_support::MemoryClient;
+use it_server::{Server, ServerHandle};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn start_server(network: &MemoryNetwork) -> ServerHandle {
+    let any_port = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+    Server::new()
+        .with_maps(Vec::new())
+        .with_start_delay(Duration::ZERO)
+        .serve(
+            network.listen(any_port).expect("Failed to listen"),
+            network.bind(any_port).expect("Failed to bind"),
+        )
+        .expect("Failed to start server")
+}
+
+fn write_script(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("it-{}-{}.txt", name, std::process::id()));
+    std::fs::write(&path, content).expect("Failed to write script");
+    path
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scripted_match_against_server() {
+    let network = MemoryNetwork::new();
+    let server = start_server(&network);
+    let script = write_script(
+        "match",
+        "join\n\
+         expect Start\n\
+         expect RoundStart\n\
+         walk 1 0 200\n\
+         chat hello\n\
+         expect Chat\n\
+         leave\n",
+    );
+    let config = HeadlessConfig {
+        script: Some(script.clone()),
+        duration: Duration::from_secs(20),
+        transport: Transport::Memory(network.clone()),
+    };
+    let server_addr = ServerAddr {
+        tcp: server.tcp_addr(),
+        udp: server.udp_addr(),
+    };
+    let headless = tokio::task::spawn_blocking(move || {
+        headless::run(config, NetSimConfig::default(), server_addr)
+    });
+
+    let mut opponent = MemoryClient::connect_memory(&network, &server).await;
+    opponent.join().await;
+    opponent.expect_start().await;
+    // The headless player reports where it walks to like the game does
+    loop {
+        if let ServerEvent::PosUpdate(_) = opponent.recv_udp().await {
+            break;
+        }
+    }
+    let ServerEvent::Chat(chat) = opponent.expect("Chat").await else {
+        unreachable!();
+    };
+    assert_eq!(chat.message, "hello");
+
+    let exit = headless.await.expect("Headless client panicked");
+    std::fs::remove_file(script).ok();
+    assert_eq!(exit, AppExit::Success);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_expect_exits_with_an_error() {
+    let network = MemoryNetwork::new();
+    let server = start_server(&network);
+    // Nobody else joins, so the game never starts
+    let script = write_script("expect", "join\nexpect Start 300\n");
+    let config = HeadlessConfig {
+        script: Some(script.clone()),
+        duration: Duration::from_secs(20),
+        transport: Transport::Memory(network),
+    };
+    let server_addr = ServerAddr {
+        tcp: server.tcp_addr(),
+        udp: server.udp_addr(),
+    };
+    let exit = tokio::task::spawn_blocking(move || {
+        headless::run(config, NetSimConfig::default(), server_addr)
+    })
+    .await
+    .expect("Headless client panicked");
+    std::fs::remove_file(script).ok();
+    assert!(exit.is_error());
+}
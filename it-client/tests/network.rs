@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use it_client::game::RoundPlugin;
+use it_client::menu::MenuState;
+use it_client::net::{
+    ConnectionState, NetworkPlugin, ServerAddr, TcpSocketSender, Transport, UdpSocketSender,
+};
+use it_client::net_sim::NetSimConfig;
+use it_client::player::{Player, SpawnPlayerEvent};
+use it_client::GameState;
+use it_core::transport::MemoryNetwork;
+use it_core::{ClientEvent, InputState, PosUpdateEvent, ServerEvent};
+use it_server::test_support::{MemoryClient, TIMEOUT};
+use it_server::{Server, ServerHandle};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+fn start_server(network: &MemoryNetwork) -> ServerHandle {
+    let any_port = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+    Server::new()
+        .with_maps(Vec::new())
+        .with_start_delay(Duration::ZERO)
+        .serve(
+            network.listen(any_port).expect("Failed to listen"),
+            network.bind(any_port).expect("Failed to bind"),
+        )
+        .expect("Failed to start server")
+}
+
+/// Players as bare entities, positions from the server land on their transform
+fn spawn_player(trigger: Trigger<SpawnPlayerEvent>, mut commands: Commands) {
+    let event = trigger.event();
+    commands.spawn((
+        Player {
+            id: event.id.clone(),
+            nickname: event.nickname.clone(),
+        },
+        Transform::from_translation(event.coords.extend(0.0)),
+    ));
+}
+
+fn client_app(network: &MemoryNetwork, server: &ServerHandle) -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, StatesPlugin))
+        .init_state::<GameState>()
+        .init_state::<MenuState>()
+        .add_plugins((NetworkPlugin, RoundPlugin))
+        .insert_resource(NetSimConfig::default())
+        .insert_resource(Transport::Memory(network.clone()))
+        .insert_resource(ServerAddr {
+            tcp: server.tcp_addr(),
+            udp: server.udp_addr(),
+        })
+        .observe(spawn_player);
+    app.finish();
+    app.cleanup();
+    app
+}
+
+/// Runs frames until `condition` holds, while the server tasks keep going
+async fn update_until(app: &mut App, what: &str, condition: impl Fn(&mut World) -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        app.update();
+        if condition(app.world_mut()) {
+            return;
+        }
+        assert!(Instant::now() < deadline, "Timed out waiting for {}", what);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+fn send(sender: &async_channel::Sender<ClientEvent>, event: ClientEvent) {
+    sender.send_blocking(event).expect("Network task stopped");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn join_start_and_relay_positions() {
+    let network = MemoryNetwork::new();
+    let server = start_server(&network);
+    let mut app = client_app(&network, &server);
+    update_until(&mut app, "the connection", |world| {
+        *world.resource::<ConnectionState>() == ConnectionState::Connected
+    })
+    .await;
+
+    send(
+        &app.world().resource::<TcpSocketSender>().0,
+        ClientEvent::Join,
+    );
+    let mut opponent = MemoryClient::connect_memory(&network, &server).await;
+    opponent.join().await;
+    let start = opponent.expect_start().await;
+    update_until(&mut app, "the game to start", |world| {
+        *world.resource::<State<GameState>>().get() == GameState::Game
+    })
+    .await;
+    let players = app.world_mut().query::<&Player>().iter(app.world()).count();
+    assert_eq!(players, start.players.len());
+
+    // From the server to the client
+    opponent.move_to(12.0, 34.0).await;
+    let opponent_id = opponent.id();
+    update_until(&mut app, "the opponent's position", |world| {
+        world
+            .query::<(&Player, &Transform)>()
+            .iter(world)
+            .any(|(player, transform)| {
+                player.id == opponent_id
+                    && transform.translation.truncate() == Vec2::new(12.0, 34.0)
+            })
+    })
+    .await;
+
+    // From the client to the server
+    let our_id = start
+        .players
+        .iter()
+        .map(|player| player.id.clone())
+        .find(|id| *id != opponent_id)
+        .expect("Client is not part of the game");
+    send(
+        &app.world().resource::<UdpSocketSender>().0,
+        ClientEvent::PosUpdate(PosUpdateEvent {
+            client_id: our_id.clone(),
+            x: 56.0,
+            y: 78.0,
+            input: InputState::default(),
+        }),
+    );
+    loop {
+        if let ServerEvent::PosUpdate(pos) = opponent.recv_udp().await {
+            if pos.client_id == our_id {
+                assert_eq!((pos.x, pos.y), (56.0, 78.0));
+                break;
+            }
+        }
+    }
+}
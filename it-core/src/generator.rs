@@ -0,0 +1,228 @@
+use crate::map::{Map, Obstacle, Wall};
+use crate::{ArenaSize, Position};
+use std::collections::VecDeque;
+
+/// Generates arenas from a seed. The same generator settings and seed always
+/// produce the same map, so only the seed has to go over the network.
+#[derive(Debug, Clone)]
+pub struct ArenaGenerator {
+    pub bounds: ArenaSize,
+    /// Side of the grid cells obstacles are laid out on
+    pub cell_size: f32,
+    /// Share of the cells covered by walls and obstacles
+    pub density: f32,
+    pub spawn_points: usize,
+}
+
+impl Default for ArenaGenerator {
+    fn default() -> Self {
+        Self {
+            bounds: ArenaSize::default(),
+            cell_size: 64.0,
+            density: 0.15,
+            spawn_points: 4,
+        }
+    }
+}
+
+impl ArenaGenerator {
+    pub fn generate(&self, seed: u64) -> Map {
+        let mut rng = SplitMix64(seed);
+        let mut grid = Grid::new(self.bounds, self.cell_size);
+        let mut map = Map {
+            name: format!("Generated #{}", seed),
+            bounds: self.bounds,
+            ..Map::default()
+        };
+
+        let target = (grid.cells.len() as f32 * self.density) as usize;
+        let mut blocked = 0;
+        for _ in 0..grid.cells.len() * 4 {
+            if blocked >= target {
+                break;
+            }
+            let start = (rng.below(grid.cols), rng.below(grid.rows));
+            if rng.chance(0.6) {
+                let length = 2 + rng.below(3);
+                let horizontal = rng.chance(0.5);
+                let cells: Vec<_> = (0..length)
+                    .map(|i| {
+                        if horizontal {
+                            (start.0 + i, start.1)
+                        } else {
+                            (start.0, start.1 + i)
+                        }
+                    })
+                    .collect();
+                if grid.try_block(&cells) {
+                    blocked += cells.len();
+                    map.walls.push(grid.wall(&cells, horizontal));
+                }
+            } else if grid.try_block(&[start]) {
+                blocked += 1;
+                map.obstacles.push(Obstacle {
+                    position: grid.center(start),
+                    radius: grid.cell_width.min(grid.cell_height) * 0.35,
+                });
+            }
+        }
+
+        map.spawn_points = grid.spread_spawn_points(self.spawn_points, &mut rng);
+        map
+    }
+}
+
+/// Occupancy grid covering the arena, used to keep every free cell reachable
+struct Grid {
+    bounds: ArenaSize,
+    cols: usize,
+    rows: usize,
+    cell_width: f32,
+    cell_height: f32,
+    cells: Vec<bool>,
+}
+
+impl Grid {
+    fn new(bounds: ArenaSize, cell_size: f32) -> Self {
+        let cols = ((bounds.width / cell_size) as usize).max(1);
+        let rows = ((bounds.height / cell_size) as usize).max(1);
+        Self {
+            bounds,
+            cols,
+            rows,
+            cell_width: bounds.width / cols as f32,
+            cell_height: bounds.height / rows as f32,
+            cells: vec![false; cols * rows],
+        }
+    }
+    fn index(&self, (col, row): (usize, usize)) -> usize {
+        row * self.cols + col
+    }
+    fn center(&self, (col, row): (usize, usize)) -> Position {
+        Position {
+            x: -self.bounds.width / 2.0 + self.cell_width * (col as f32 + 0.5),
+            y: -self.bounds.height / 2.0 + self.cell_height * (row as f32 + 0.5),
+        }
+    }
+    fn wall(&self, cells: &[(usize, usize)], horizontal: bool) -> Wall {
+        let first = self.center(cells[0]);
+        let last = self.center(cells[cells.len() - 1]);
+        let length = cells.len() as f32;
+        let (width, height) = if horizontal {
+            (self.cell_width * length, self.cell_height * 0.5)
+        } else {
+            (self.cell_width * 0.5, self.cell_height * length)
+        };
+        Wall {
+            position: Position {
+                x: (first.x + last.x) / 2.0,
+                y: (first.y + last.y) / 2.0,
+            },
+            width,
+            height,
+        }
+    }
+    /// Blocks the cells if they are in bounds, free, and blocking them keeps the free cells connected
+    fn try_block(&mut self, cells: &[(usize, usize)]) -> bool {
+        if cells.iter().any(|&(col, row)| {
+            col >= self.cols || row >= self.rows || self.cells[self.index((col, row))]
+        }) {
+            return false;
+        }
+        for &cell in cells {
+            let index = self.index(cell);
+            self.cells[index] = true;
+        }
+        if self.is_connected() {
+            return true;
+        }
+        for &cell in cells {
+            let index = self.index(cell);
+            self.cells[index] = false;
+        }
+        false
+    }
+    /// Whether every free cell can be reached from any other, going across the wrapping edges
+    fn is_connected(&self) -> bool {
+        let free = self.cells.iter().filter(|blocked| !**blocked).count();
+        let Some(start) = self.cells.iter().position(|blocked| !blocked) else {
+            return false;
+        };
+        let mut visited = vec![false; self.cells.len()];
+        visited[start] = true;
+        let mut queue = VecDeque::from([start]);
+        let mut reached = 1;
+        while let Some(index) = queue.pop_front() {
+            let (col, row) = (index % self.cols, index / self.cols);
+            let neighbours = [
+                ((col + 1) % self.cols, row),
+                ((col + self.cols - 1) % self.cols, row),
+                (col, (row + 1) % self.rows),
+                (col, (row + self.rows - 1) % self.rows),
+            ];
+            for neighbour in neighbours {
+                let neighbour = self.index(neighbour);
+                if !visited[neighbour] && !self.cells[neighbour] {
+                    visited[neighbour] = true;
+                    reached += 1;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        reached == free
+    }
+    /// Picks free cells as far as possible from each other
+    fn spread_spawn_points(&self, count: usize, rng: &mut SplitMix64) -> Vec<Position> {
+        let free: Vec<Position> = (0..self.cells.len())
+            .filter(|&index| !self.cells[index])
+            .map(|index| self.center((index % self.cols, index / self.cols)))
+            .collect();
+        if free.is_empty() {
+            return Vec::new();
+        }
+
+        let mut spawn_points = vec![free[rng.below(free.len())].clone()];
+        while spawn_points.len() < count.min(free.len()) {
+            let farthest = free
+                .iter()
+                .map(|candidate| {
+                    let closest = spawn_points
+                        .iter()
+                        .map(|spawn| self.bounds.distance(candidate, spawn))
+                        .fold(f32::MAX, f32::min);
+                    (candidate, closest)
+                })
+                .fold(
+                    None,
+                    |best: Option<(&Position, f32)>, (candidate, distance)| match best {
+                        Some((_, best_distance)) if best_distance >= distance => best,
+                        _ => Some((candidate, distance)),
+                    },
+                );
+            match farthest {
+                Some((candidate, _)) => spawn_points.push(candidate.clone()),
+                None => break,
+            }
+        }
+        spawn_points
+    }
+}
+
+/// Small PRNG with a stable output across platforms and releases, unlike `rand`'s
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+    fn chance(&mut self, probability: f32) -> bool {
+        ((self.next_u64() >> 40) as f32 / (1u64 << 24) as f32) < probability
+    }
+}
@@ -1,8 +1,19 @@
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub mod generator;
+pub mod map;
+pub mod transport;
+
+pub use generator::ArenaGenerator;
+pub use map::Map;
 
 pub type LobbyId = String;
 pub type ClientId = String;
 
+/// Time after a tag during which the new 'it' can't tag anyone back
+pub const TAG_COOLDOWN: Duration = Duration::from_secs(2);
+
 pub trait IntoResponse {
     fn into_response(self) -> String;
 }
@@ -10,24 +21,97 @@ pub trait IntoResponse {
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Player {
     pub id: ClientId,
+    pub nickname: String,
     /// Number of times the player has been 'it'
     pub it_count: usize,
     pub position: Position,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
 }
 
+impl Position {
+    pub fn distance(&self, other: &Position) -> f32 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
+}
+
+/// Size of the playable arena in world units, centered on the origin.
+/// Players leaving one edge come back on the opposite one.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct ArenaSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for ArenaSize {
+    fn default() -> Self {
+        Self {
+            width: 1280.0,
+            height: 720.0,
+        }
+    }
+}
+
+impl ArenaSize {
+    /// Wraps a position that went past an edge to the opposite edge
+    pub fn wrap(&self, position: &Position) -> Position {
+        Position {
+            x: wrap_axis(position.x, self.width / 2.0),
+            y: wrap_axis(position.y, self.height / 2.0),
+        }
+    }
+    /// Shortest distance between two positions, going across edges if closer
+    pub fn distance(&self, a: &Position, b: &Position) -> f32 {
+        let dx = (a.x - b.x).abs() % self.width;
+        let dy = (a.y - b.y).abs() % self.height;
+        let dx = dx.min(self.width - dx);
+        let dy = dy.min(self.height - dy);
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+fn wrap_axis(value: f32, half_extent: f32) -> f32 {
+    if value > half_extent {
+        -half_extent
+    } else if value < -half_extent {
+        half_extent
+    } else {
+        value
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientEvent {
     Join,
+    Leave,
     UdpUpgrade(UdpUpgradeEvent),
     PosUpdate(PosUpdateEvent),
+    Pong(PingEvent),
+    Ping(PingEvent),
+    Chat(ChatMessageEvent),
+    Emote(EmoteEvent),
 }
+impl ClientEvent {
+    /// Name of the variant, as found in the `type` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientEvent::Join => "Join",
+            ClientEvent::Leave => "Leave",
+            ClientEvent::UdpUpgrade(_) => "UdpUpgrade",
+            ClientEvent::PosUpdate(_) => "PosUpdate",
+            ClientEvent::Pong(_) => "Pong",
+            ClientEvent::Ping(_) => "Ping",
+            ClientEvent::Chat(_) => "Chat",
+            ClientEvent::Emote(_) => "Emote",
+        }
+    }
+}
+
 impl IntoResponse for ClientEvent {
     fn into_response(self) -> String {
         let mut str = serde_json::to_string(&self).unwrap();
@@ -46,6 +130,72 @@ pub struct PosUpdateEvent {
     pub client_id: ClientId,
     pub x: f32,
     pub y: f32,
+    /// Actions held by the player when the position was sent
+    #[serde(default)]
+    pub input: InputState,
+}
+
+/// Longest chat message the server accepts, in characters
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+/// Chat message sent by a client to its lobby
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatMessageEvent {
+    pub message: String,
+}
+
+/// Chat message relayed to everyone in the lobby
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ChatEvent {
+    /// `None` for notices from the server
+    pub client_id: Option<ClientId>,
+    pub nickname: String,
+    pub message: String,
+}
+
+/// Minimum time between two emotes of the same player
+pub const EMOTE_COOLDOWN: Duration = Duration::from_secs(2);
+
+/// Quick messages players can send without typing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Emote {
+    Hello,
+    Laugh,
+    Angry,
+    GoodGame,
+}
+
+impl Emote {
+    pub const ALL: [Emote; 4] = [Emote::Hello, Emote::Laugh, Emote::Angry, Emote::GoodGame];
+
+    /// Text shown in the speech bubble
+    pub fn text(self) -> &'static str {
+        match self {
+            Emote::Hello => "Hi!",
+            Emote::Laugh => "Haha",
+            Emote::Angry => ">:(",
+            Emote::GoodGame => "GG",
+        }
+    }
+}
+
+/// Emote sent over UDP, relayed to the whole lobby including the sender
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmoteEvent {
+    pub client_id: ClientId,
+    pub emote: Emote,
+}
+
+/// Snapshot of a player's input actions
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    /// Analog movement axis, with a length of at most 1
+    pub move_x: f32,
+    pub move_y: f32,
+    #[serde(default)]
+    pub dash: bool,
+    #[serde(default)]
+    pub emote: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -56,6 +206,35 @@ pub enum ServerEvent {
     Accept(AcceptEvent),
     Leave(LeaveEvent),
     PosUpdate(PosUpdateEvent),
+    RoundStart(RoundStartEvent),
+    RoundEnd(ScoreboardEvent),
+    Tag(TagEvent),
+    Scoreboard(ScoreboardEvent),
+    Ping(PingEvent),
+    Pong(PingEvent),
+    Chat(ChatEvent),
+    Emote(EmoteEvent),
+}
+
+impl ServerEvent {
+    /// Name of the variant, as found in the `type` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Start(_) => "Start",
+            ServerEvent::Wait => "Wait",
+            ServerEvent::Accept(_) => "Accept",
+            ServerEvent::Leave(_) => "Leave",
+            ServerEvent::PosUpdate(_) => "PosUpdate",
+            ServerEvent::RoundStart(_) => "RoundStart",
+            ServerEvent::RoundEnd(_) => "RoundEnd",
+            ServerEvent::Tag(_) => "Tag",
+            ServerEvent::Scoreboard(_) => "Scoreboard",
+            ServerEvent::Ping(_) => "Ping",
+            ServerEvent::Pong(_) => "Pong",
+            ServerEvent::Chat(_) => "Chat",
+            ServerEvent::Emote(_) => "Emote",
+        }
+    }
 }
 
 impl IntoResponse for ServerEvent {
@@ -77,6 +256,25 @@ pub struct StartEvent {
     pub lobby_id: LobbyId,
     pub client_id: ClientId,
     pub players: Vec<Player>,
+    pub map: MapSource,
+}
+
+/// How clients get the map of a game
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub enum MapSource {
+    /// Handcrafted map, sent in full
+    Map(Map),
+    /// Map generated by the default [`ArenaGenerator`] from this seed
+    Seed(u64),
+}
+
+impl MapSource {
+    pub fn resolve(&self) -> Map {
+        match self {
+            MapSource::Map(map) => map.clone(),
+            MapSource::Seed(seed) => ArenaGenerator::default().generate(*seed),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -88,3 +286,39 @@ pub struct LeaveEvent {
 pub struct ClientInitEvent {
     pub client_id: ClientId,
 }
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RoundStartEvent {
+    /// Player who is 'it' when the round starts
+    pub it: ClientId,
+    pub duration_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TagEvent {
+    /// Player who just became 'it'
+    pub it: ClientId,
+    pub tagged_by: ClientId,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScoreboardEvent {
+    pub players: Vec<PlayerScore>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PlayerScore {
+    pub client_id: ClientId,
+    pub nickname: String,
+    pub it_count: usize,
+    /// Round trip time measured by the server, if known
+    pub ping_ms: Option<u32>,
+}
+
+/// Echoed back by the other side to measure round trip time. The server pings
+/// clients over TCP, clients probe the server over UDP
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+pub struct PingEvent {
+    /// Milliseconds since the sender started
+    pub sent_at: u64,
+}
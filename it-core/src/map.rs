@@ -0,0 +1,75 @@
+use crate::{ArenaSize, Position};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::Path;
+
+/// Arena layout, stored as JSON files
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct Map {
+    pub name: String,
+    pub bounds: ArenaSize,
+    #[serde(default)]
+    pub walls: Vec<Wall>,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default)]
+    pub spawn_points: Vec<Position>,
+}
+
+/// Axis aligned rectangle players can't go through
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Wall {
+    /// Center of the wall
+    pub position: Position,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Round obstacle players can't go through
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Obstacle {
+    /// Center of the obstacle
+    pub position: Position,
+    pub radius: f32,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::Io(e) => write!(f, "IO error: {}", e),
+            MapError::Json(e) => write!(f, "JSON error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+impl From<std::io::Error> for MapError {
+    fn from(e: std::io::Error) -> Self {
+        MapError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for MapError {
+    fn from(e: serde_json::Error) -> Self {
+        MapError::Json(e)
+    }
+}
+
+impl Map {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MapError> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MapError> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
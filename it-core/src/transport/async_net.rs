@@ -0,0 +1,64 @@
+//! TCP and UDP sockets of `async-net`, which work with any executor such as
+//! the task pools of the client
+
+use super::{trim_line, ReliableReceiver, ReliableSender, UnreliableSocket};
+use async_net::{AsyncToSocketAddrs, TcpStream, UdpSocket};
+use futures_lite::io::BufReader;
+use futures_lite::{AsyncBufReadExt, AsyncWriteExt};
+use std::io;
+use std::net::SocketAddr;
+
+pub struct TcpSender(TcpStream);
+
+impl ReliableSender for TcpSender {
+    async fn send(&mut self, msg: &str) -> io::Result<()> {
+        if msg.ends_with('\n') {
+            self.0.write_all(msg.as_bytes()).await?;
+        } else {
+            self.0.write_all(format!("{}\n", msg).as_bytes()).await?;
+        }
+        self.0.flush().await
+    }
+}
+
+pub struct TcpReceiver {
+    reader: BufReader<TcpStream>,
+    line: String,
+}
+
+impl ReliableReceiver for TcpReceiver {
+    async fn recv(&mut self) -> io::Result<Option<String>> {
+        self.line.clear();
+        if self.reader.read_line(&mut self.line).await? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(trim_line(std::mem::take(&mut self.line))))
+    }
+}
+
+/// Splits a stream into the halves of a reliable connection
+pub fn split(stream: TcpStream) -> (TcpSender, TcpReceiver) {
+    (
+        TcpSender(stream.clone()),
+        TcpReceiver {
+            reader: BufReader::new(stream),
+            line: String::new(),
+        },
+    )
+}
+
+pub async fn connect(addr: impl AsyncToSocketAddrs) -> io::Result<(TcpSender, TcpReceiver)> {
+    Ok(split(TcpStream::connect(addr).await?))
+}
+
+impl UnreliableSocket for UdpSocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+    async fn send_to(&self, msg: &[u8], addr: SocketAddr) -> io::Result<()> {
+        UdpSocket::send_to(self, msg, addr).await.map(|_| ())
+    }
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf).await
+    }
+}
@@ -0,0 +1,241 @@
+//! In-process network where connections and sockets are channels. Datagrams
+//! can be dropped on purpose, each socket deciding with its own generator
+//! seeded from the network seed and its port, so a test sending the same
+//! datagrams loses the same ones on every run.
+
+use super::{Listener, ReliableReceiver, ReliableSender, UnreliableSocket};
+use async_channel::{unbounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+/// First port handed out to listeners and sockets bound to port 0
+const FIRST_EPHEMERAL_PORT: u16 = 49152;
+
+type Connection = (MemorySender, MemoryReceiver, SocketAddr);
+type Datagram = (Vec<u8>, SocketAddr);
+
+struct Network {
+    listeners: HashMap<SocketAddr, Sender<Connection>>,
+    sockets: HashMap<SocketAddr, Sender<Datagram>>,
+    next_port: u16,
+    /// Chance between 0 and 1 for a datagram to get lost
+    loss: f64,
+    seed: u64,
+}
+
+impl Network {
+    fn allocate(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+        if addr.port() != 0 {
+            if self.listeners.contains_key(&addr) || self.sockets.contains_key(&addr) {
+                return Err(io::ErrorKind::AddrInUse.into());
+            }
+            return Ok(addr);
+        }
+        loop {
+            let addr = SocketAddr::new(addr.ip(), self.next_port);
+            self.next_port = self
+                .next_port
+                .checked_add(1)
+                .unwrap_or(FIRST_EPHEMERAL_PORT);
+            if !self.listeners.contains_key(&addr) && !self.sockets.contains_key(&addr) {
+                return Ok(addr);
+            }
+        }
+    }
+}
+
+/// Handle to an in-memory network, clones share the same network
+#[derive(Clone)]
+pub struct MemoryNetwork(Arc<Mutex<Network>>);
+
+impl Default for MemoryNetwork {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Network {
+            listeners: HashMap::new(),
+            sockets: HashMap::new(),
+            next_port: FIRST_EPHEMERAL_PORT,
+            loss: 0.0,
+            seed: 0,
+        })))
+    }
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loses datagrams with the given chance, using `seed` to pick which ones
+    pub fn with_loss(self, loss: f64, seed: u64) -> Self {
+        {
+            let mut network = self.0.lock().unwrap();
+            network.loss = loss;
+            network.seed = seed;
+        }
+        self
+    }
+
+    /// Changes the chance for datagrams to get lost, for sockets bound from now on
+    /// and the ones already bound
+    pub fn set_loss(&self, loss: f64) {
+        self.0.lock().unwrap().loss = loss;
+    }
+
+    /// Starts accepting connections on `addr`, port 0 picks a free one
+    pub fn listen(&self, addr: SocketAddr) -> io::Result<MemoryListener> {
+        let mut network = self.0.lock().unwrap();
+        let addr = network.allocate(addr)?;
+        let (sender, incoming) = unbounded();
+        network.listeners.insert(addr, sender);
+        Ok(MemoryListener {
+            addr,
+            incoming,
+            network: self.clone(),
+        })
+    }
+
+    /// Opens a connection to a listener
+    pub async fn connect(&self, addr: SocketAddr) -> io::Result<(MemorySender, MemoryReceiver)> {
+        let (listener, local_addr) = {
+            let mut network = self.0.lock().unwrap();
+            let listener = network
+                .listeners
+                .get(&addr)
+                .cloned()
+                .ok_or(io::ErrorKind::ConnectionRefused)?;
+            let local_addr = network.allocate(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))?;
+            (listener, local_addr)
+        };
+        let (to_server, server_incoming) = unbounded();
+        let (to_client, client_incoming) = unbounded();
+        listener
+            .send((
+                MemorySender(to_client),
+                MemoryReceiver(server_incoming),
+                local_addr,
+            ))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::ConnectionRefused))?;
+        Ok((MemorySender(to_server), MemoryReceiver(client_incoming)))
+    }
+
+    /// Binds a datagram socket to `addr`, port 0 picks a free one
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<MemorySocket> {
+        let mut network = self.0.lock().unwrap();
+        let addr = network.allocate(addr)?;
+        let (sender, incoming) = unbounded();
+        network.sockets.insert(addr, sender);
+        // Never zero, which xorshift can't get out of
+        let rng = (network.seed ^ u64::from(addr.port())).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        Ok(MemorySocket {
+            addr,
+            incoming,
+            rng: Mutex::new(rng),
+            network: self.clone(),
+        })
+    }
+}
+
+pub struct MemorySender(Sender<String>);
+
+impl ReliableSender for MemorySender {
+    async fn send(&mut self, msg: &str) -> io::Result<()> {
+        let msg = msg.strip_suffix('\n').unwrap_or(msg).to_string();
+        self.0
+            .send(msg)
+            .await
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+pub struct MemoryReceiver(Receiver<String>);
+
+impl ReliableReceiver for MemoryReceiver {
+    async fn recv(&mut self) -> io::Result<Option<String>> {
+        Ok(self.0.recv().await.ok())
+    }
+}
+
+pub struct MemoryListener {
+    addr: SocketAddr,
+    incoming: Receiver<Connection>,
+    network: MemoryNetwork,
+}
+
+impl Listener for MemoryListener {
+    type Sender = MemorySender;
+    type Receiver = MemoryReceiver;
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+    async fn accept(&mut self) -> io::Result<Connection> {
+        self.incoming
+            .recv()
+            .await
+            .map_err(|_| io::ErrorKind::NotConnected.into())
+    }
+}
+
+impl Drop for MemoryListener {
+    fn drop(&mut self) {
+        self.network.0.lock().unwrap().listeners.remove(&self.addr);
+    }
+}
+
+pub struct MemorySocket {
+    addr: SocketAddr,
+    incoming: Receiver<Datagram>,
+    /// Xorshift state deciding which datagrams get lost
+    rng: Mutex<u64>,
+    network: MemoryNetwork,
+}
+
+impl MemorySocket {
+    /// Random number between 0 and 1
+    fn roll(&self) -> f64 {
+        let mut rng = self.rng.lock().unwrap();
+        *rng ^= *rng << 13;
+        *rng ^= *rng >> 7;
+        *rng ^= *rng << 17;
+        (*rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl UnreliableSocket for MemorySocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+    async fn send_to(&self, msg: &[u8], addr: SocketAddr) -> io::Result<()> {
+        let (loss, destination) = {
+            let network = self.network.0.lock().unwrap();
+            (network.loss, network.sockets.get(&addr).cloned())
+        };
+        // Like UDP, nothing tells the sender the datagram went nowhere
+        if self.roll() >= loss {
+            if let Some(destination) = destination {
+                let _ = destination.try_send((msg.to_vec(), self.addr));
+            }
+        }
+        Ok(())
+    }
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (msg, from) = self
+            .incoming
+            .recv()
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::NotConnected))?;
+        // Datagrams too long for the buffer get cut, as with UDP
+        let len = msg.len().min(buf.len());
+        buf[..len].copy_from_slice(&msg[..len]);
+        Ok((len, from))
+    }
+}
+
+impl Drop for MemorySocket {
+    fn drop(&mut self) {
+        self.network.0.lock().unwrap().sockets.remove(&self.addr);
+    }
+}
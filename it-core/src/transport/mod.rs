@@ -0,0 +1,75 @@
+//! How events travel between clients and the server, either over a reliable
+//! and ordered connection (TCP) or as datagrams that can get lost (UDP).
+//!
+//! Messages are single lines of JSON such as the ones made by
+//! [`IntoResponse`](crate::IntoResponse), implementations take care of
+//! framing them. Besides real sockets, behind the `tokio` and `async-net`
+//! features, [`memory`] connects everything in process so tests need no ports.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+#[cfg(feature = "async-net")]
+pub mod async_net;
+pub mod memory;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+pub use memory::MemoryNetwork;
+
+/// Sending half of a reliable connection
+pub trait ReliableSender: Send + 'static {
+    /// Sends one message, the trailing newline is optional
+    fn send(&mut self, msg: &str) -> impl Future<Output = io::Result<()>> + Send;
+}
+
+/// Receiving half of a reliable connection
+pub trait ReliableReceiver: Send + 'static {
+    /// Next message without its newline, `None` once the other side closed the connection
+    fn recv(&mut self) -> impl Future<Output = io::Result<Option<String>>> + Send;
+}
+
+/// Accepts reliable connections on the server
+pub trait Listener: Send + 'static {
+    type Sender: ReliableSender;
+    type Receiver: ReliableReceiver;
+
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+    /// Waits for the next client, returning both halves of its connection and its address
+    fn accept(
+        &mut self,
+    ) -> impl Future<Output = io::Result<(Self::Sender, Self::Receiver, SocketAddr)>> + Send;
+}
+
+/// Datagrams that can get lost on the way
+pub trait UnreliableSocket: Send + Sync + 'static {
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+    fn send_to(&self, msg: &[u8], addr: SocketAddr) -> impl Future<Output = io::Result<()>> + Send;
+    /// Waits for the next datagram, returning its length and sender
+    fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<(usize, SocketAddr)>> + Send;
+}
+
+impl<T: UnreliableSocket> UnreliableSocket for Arc<T> {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        T::local_addr(self)
+    }
+    async fn send_to(&self, msg: &[u8], addr: SocketAddr) -> io::Result<()> {
+        T::send_to(self, msg, addr).await
+    }
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        T::recv_from(self, buf).await
+    }
+}
+
+/// Removes the line ending of a received line
+fn trim_line(mut line: String) -> String {
+    while line.ends_with(['\n', '\r']) {
+        line.pop();
+    }
+    line
+}
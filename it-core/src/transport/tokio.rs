@@ -0,0 +1,77 @@
+//! TCP and UDP sockets of the tokio runtime, used by the server
+
+use super::{trim_line, Listener, ReliableReceiver, ReliableSender, UnreliableSocket};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+
+pub struct TcpSender(OwnedWriteHalf);
+
+impl ReliableSender for TcpSender {
+    async fn send(&mut self, msg: &str) -> io::Result<()> {
+        if msg.ends_with('\n') {
+            self.0.write_all(msg.as_bytes()).await
+        } else {
+            self.0.write_all(format!("{}\n", msg).as_bytes()).await
+        }
+    }
+}
+
+pub struct TcpReceiver {
+    reader: BufReader<OwnedReadHalf>,
+    line: String,
+}
+
+impl ReliableReceiver for TcpReceiver {
+    async fn recv(&mut self) -> io::Result<Option<String>> {
+        self.line.clear();
+        if self.reader.read_line(&mut self.line).await? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(trim_line(std::mem::take(&mut self.line))))
+    }
+}
+
+/// Splits a stream into the halves of a reliable connection
+pub fn split(stream: TcpStream) -> (TcpSender, TcpReceiver) {
+    let (reader, writer) = stream.into_split();
+    (
+        TcpSender(writer),
+        TcpReceiver {
+            reader: BufReader::new(reader),
+            line: String::new(),
+        },
+    )
+}
+
+pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<(TcpSender, TcpReceiver)> {
+    Ok(split(TcpStream::connect(addr).await?))
+}
+
+impl Listener for TcpListener {
+    type Sender = TcpSender;
+    type Receiver = TcpReceiver;
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        TcpListener::local_addr(self)
+    }
+    async fn accept(&mut self) -> io::Result<(TcpSender, TcpReceiver, SocketAddr)> {
+        let (stream, addr) = TcpListener::accept(self).await?;
+        let (sender, receiver) = split(stream);
+        Ok((sender, receiver, addr))
+    }
+}
+
+impl UnreliableSocket for UdpSocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+    async fn send_to(&self, msg: &[u8], addr: SocketAddr) -> io::Result<()> {
+        UdpSocket::send_to(self, msg, addr).await.map(|_| ())
+    }
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buf).await
+    }
+}
@@ -0,0 +1,116 @@
+//! Client and server each rebuild generated maps from the seed, so these pin
+//! down what both sides rely on.
+
+use it_core::map::{Obstacle, Wall};
+use it_core::{ArenaGenerator, Map, Position};
+use std::collections::VecDeque;
+
+/// Half the side of a player's collider, spawn points need this much room
+const PLAYER_HALF_SIZE: f32 = 12.0;
+const SEEDS: std::ops::Range<u64> = 0..64;
+
+fn in_wall(wall: &Wall, point: &Position, margin: f32) -> bool {
+    (point.x - wall.position.x).abs() < wall.width / 2.0 + margin
+        && (point.y - wall.position.y).abs() < wall.height / 2.0 + margin
+}
+
+fn in_obstacle(obstacle: &Obstacle, point: &Position, margin: f32) -> bool {
+    let dx = point.x - obstacle.position.x;
+    let dy = point.y - obstacle.position.y;
+    (dx * dx + dy * dy).sqrt() < obstacle.radius + margin
+}
+
+fn is_floor(map: &Map, point: &Position, margin: f32) -> bool {
+    !map.walls.iter().any(|wall| in_wall(wall, point, margin))
+        && !map
+            .obstacles
+            .iter()
+            .any(|obstacle| in_obstacle(obstacle, point, margin))
+}
+
+#[test]
+fn same_seed_gives_the_same_map() {
+    let generator = ArenaGenerator::default();
+    for seed in SEEDS {
+        assert_eq!(generator.generate(seed), generator.generate(seed));
+    }
+    assert_ne!(generator.generate(1), generator.generate(2));
+}
+
+#[test]
+fn generated_floors_are_connected() {
+    let generator = ArenaGenerator::default();
+    let bounds = generator.bounds;
+    let cols = (bounds.width / generator.cell_size) as usize;
+    let rows = (bounds.height / generator.cell_size) as usize;
+    let cell_width = bounds.width / cols as f32;
+    let cell_height = bounds.height / rows as f32;
+    let center = |col: usize, row: usize| Position {
+        x: -bounds.width / 2.0 + cell_width * (col as f32 + 0.5),
+        y: -bounds.height / 2.0 + cell_height * (row as f32 + 0.5),
+    };
+
+    for seed in SEEDS {
+        let map = generator.generate(seed);
+        let floor: Vec<bool> = (0..cols * rows)
+            .map(|index| is_floor(&map, &center(index % cols, index / cols), 0.0))
+            .collect();
+        let start = floor.iter().position(|free| *free).expect("No floor");
+
+        // Walking from cell to cell, across the wrapping edges
+        let mut reached = vec![false; floor.len()];
+        reached[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            let (col, row) = (index % cols, index / cols);
+            let neighbours = [
+                ((col + 1) % cols, row),
+                ((col + cols - 1) % cols, row),
+                (col, (row + 1) % rows),
+                (col, (row + rows - 1) % rows),
+            ];
+            for (next_col, next_row) in neighbours {
+                let next = next_row * cols + next_col;
+                if reached[next] || !floor[next] {
+                    continue;
+                }
+                // Halfway between the cells, skipped when wrapping around
+                let (from, to) = (center(col, row), center(next_col, next_row));
+                let midpoint = Position {
+                    x: (from.x + to.x) / 2.0,
+                    y: (from.y + to.y) / 2.0,
+                };
+                let wraps = (from.x - to.x).abs() > cell_width * 1.5
+                    || (from.y - to.y).abs() > cell_height * 1.5;
+                if wraps || is_floor(&map, &midpoint, 0.0) {
+                    reached[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        let unreachable = (0..floor.len())
+            .filter(|&index| floor[index] && !reached[index])
+            .count();
+        assert_eq!(unreachable, 0, "Seed {} has unreachable floor", seed);
+    }
+}
+
+#[test]
+fn spawn_points_are_on_floor() {
+    let generator = ArenaGenerator::default();
+    for seed in SEEDS {
+        let map = generator.generate(seed);
+        assert_eq!(map.spawn_points.len(), generator.spawn_points);
+        for spawn in &map.spawn_points {
+            assert!(
+                is_floor(&map, spawn, PLAYER_HALF_SIZE),
+                "Seed {} spawns at ({}, {}) inside a wall or obstacle",
+                seed,
+                spawn.x,
+                spawn.y
+            );
+            assert!(spawn.x.abs() <= map.bounds.width / 2.0);
+            assert!(spawn.y.abs() <= map.bounds.height / 2.0);
+        }
+    }
+}
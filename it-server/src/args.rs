@@ -0,0 +1,8 @@
+//! Command line helpers shared by the server and the `client` and `netsim` tools
+
+/// Value following `flag` on the command line
+pub fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == flag)?;
+    args.next()
+}
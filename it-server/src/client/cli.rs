@@ -0,0 +1,327 @@
+//! Single client driven by commands, one per line. Every event the server sends
+//! is printed with the time since the client started.
+//!
+//! Commands are typed in, piped through stdin or read from `--script <file>`.
+//! Outside of a terminal a failing command stops the client with an error, so
+//! scripted reproductions fail loudly.
+
+use crate::args::arg_value;
+use crate::Error;
+use it_core::{
+    ChatMessageEvent, ClientEvent, ClientId, Emote, EmoteEvent, InputState, IntoResponse,
+    PingEvent, PosUpdateEvent, ServerEvent, UdpUpgradeEvent,
+};
+use std::collections::VecDeque;
+use std::io::IsTerminal;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+const USAGE: &str = "\
+join                  join a lobby, upgrades to UDP once accepted
+leave                 leave the lobby
+move <x> <y>          send a position over UDP
+chat <message>        send a chat message
+emote <name>          send hello, laugh, angry or gg
+ping                  probe the server over UDP
+upgrade               send the UDP upgrade again
+tcp <json>            send raw JSON over TCP, same as a line starting with `{`
+udp <json>            send raw JSON over UDP
+wait <ms>             pause while still printing events
+expect <event> [ms]   wait for an event such as Start, fails after 5s by default
+quit";
+
+const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Received event names kept for `expect`
+const EXPECT_BACKLOG: usize = 1000;
+
+enum Incoming {
+    Event(&'static str, ServerEvent),
+    /// Message that isn't a valid event
+    Invalid(&'static str, String),
+    Disconnected,
+}
+
+struct Client {
+    started: Instant,
+    writer: OwnedWriteHalf,
+    socket: Arc<UdpSocket>,
+    client_id: Option<ClientId>,
+    incoming: mpsc::UnboundedReceiver<Incoming>,
+    /// Names of received events not matched by an `expect` yet, so events
+    /// arriving before the command are not missed
+    unexpected: VecDeque<&'static str>,
+}
+
+pub async fn run() -> Result<(), Error> {
+    let server_tcp = arg_value("--server-tcp").unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let server_udp = arg_value("--server-udp").unwrap_or_else(|| "127.0.0.1:8081".to_string());
+    let script = arg_value("--script");
+    let interactive = script.is_none() && std::io::stdin().is_terminal();
+
+    let input: Box<dyn AsyncRead + Unpin + Send> = match script {
+        Some(path) => Box::new(tokio::fs::File::open(path).await?),
+        None => Box::new(tokio::io::stdin()),
+    };
+    let mut commands = BufReader::new(input).lines();
+
+    let (reader, writer) = TcpStream::connect(&server_tcp).await?.into_split();
+    let socket = Arc::new(UdpSocket::bind("0.0.0.0:0").await?);
+    socket.connect(&server_udp).await?;
+
+    let (incoming_sender, incoming) = mpsc::unbounded_channel();
+    tokio::spawn(read_tcp(reader, incoming_sender.clone()));
+    tokio::spawn(read_udp(socket.clone(), incoming_sender));
+
+    let mut client = Client {
+        started: Instant::now(),
+        writer,
+        socket,
+        client_id: None,
+        incoming,
+        unexpected: VecDeque::new(),
+    };
+    if interactive {
+        println!("Connected to {}, type `help` for commands", server_tcp);
+    }
+
+    loop {
+        tokio::select! {
+            incoming = client.incoming.recv() => {
+                client.on_incoming(incoming.unwrap_or(Incoming::Disconnected)).await?;
+            }
+            line = commands.next_line() => {
+                let Some(line) = line? else {
+                    break;
+                };
+                if !interactive {
+                    client.print(&format!("> {}", line));
+                }
+                match client.run_command(line.trim()).await {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(e) if interactive => eprintln!("{}", e),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn read_tcp(reader: OwnedReadHalf, sender: mpsc::UnboundedSender<Incoming>) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let incoming = match serde_json::from_str::<ServerEvent>(&line) {
+            Ok(event) => Incoming::Event("tcp", event),
+            Err(_) => Incoming::Invalid("tcp", line),
+        };
+        if sender.send(incoming).is_err() {
+            return;
+        }
+    }
+    let _ = sender.send(Incoming::Disconnected);
+}
+
+async fn read_udp(socket: Arc<UdpSocket>, sender: mpsc::UnboundedSender<Incoming>) {
+    let mut buf = [0u8; 1024];
+    while let Ok(len) = socket.recv(&mut buf).await {
+        let msg = String::from_utf8_lossy(&buf[..len]);
+        let incoming = match serde_json::from_str::<ServerEvent>(&msg) {
+            Ok(event) => Incoming::Event("udp", event),
+            Err(_) => Incoming::Invalid("udp", msg.into_owned()),
+        };
+        if sender.send(incoming).is_err() {
+            return;
+        }
+    }
+}
+
+impl Client {
+    fn print(&self, message: &str) {
+        println!(
+            "[{:>9.3}s] {}",
+            self.started.elapsed().as_secs_f64(),
+            message
+        );
+    }
+
+    async fn send_tcp(&mut self, msg: &str) -> Result<(), Error> {
+        self.writer.write_all(msg.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn send_udp(&self, msg: &str) -> Result<(), Error> {
+        self.socket.send(msg.as_bytes()).await?;
+        Ok(())
+    }
+
+    fn client_id(&self) -> Result<ClientId, Error> {
+        self.client_id
+            .clone()
+            .ok_or_else(|| Error::InvalidCommand("not accepted in a lobby yet".to_string()))
+    }
+
+    async fn udp_upgrade(&self) -> Result<(), Error> {
+        let event = ClientEvent::UdpUpgrade(UdpUpgradeEvent {
+            client_id: self.client_id()?,
+        });
+        self.send_udp(&event.into_response()).await
+    }
+
+    async fn on_incoming(&mut self, incoming: Incoming) -> Result<(), Error> {
+        let (via, event) = match incoming {
+            Incoming::Event(via, event) => (via, event),
+            Incoming::Invalid(via, msg) => {
+                self.print(&format!("{} invalid event: {}", via, msg.trim()));
+                return Ok(());
+            }
+            Incoming::Disconnected => return Err(Error::Disconnected),
+        };
+        self.print(&format!(
+            "{} {} {}",
+            via,
+            event.name(),
+            serde_json::to_string_pretty(&event)?
+        ));
+
+        if self.unexpected.len() == EXPECT_BACKLOG {
+            self.unexpected.pop_front();
+        }
+        self.unexpected.push_back(event.name());
+
+        match event {
+            ServerEvent::Accept(accept) => {
+                self.client_id = Some(accept.client_id);
+                self.udp_upgrade().await?;
+            }
+            ServerEvent::Start(start) => {
+                self.client_id = Some(start.client_id);
+            }
+            // Answered so the server keeps showing our ping
+            ServerEvent::Ping(ping) => {
+                self.send_tcp(&ClientEvent::Pong(ping).into_response())
+                    .await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handles events until `deadline`, or until an event named `expected` comes in
+    async fn wait_until(
+        &mut self,
+        deadline: Instant,
+        expected: Option<&str>,
+    ) -> Result<bool, Error> {
+        loop {
+            if let Some(expected) = expected {
+                let position = self
+                    .unexpected
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(expected));
+                if let Some(position) = position {
+                    self.unexpected.drain(..=position);
+                    return Ok(true);
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => return Ok(false),
+                incoming = self.incoming.recv() => {
+                    self.on_incoming(incoming.unwrap_or(Incoming::Disconnected)).await?;
+                }
+            }
+        }
+    }
+
+    /// Runs a command, returns `false` once the client should stop
+    async fn run_command(&mut self, line: &str) -> Result<bool, Error> {
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(true);
+        }
+        if line.starts_with('{') {
+            self.send_tcp(&format!("{}\n", line)).await?;
+            return Ok(true);
+        }
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        let invalid = |message: &str| Error::InvalidCommand(format!("{} ({})", message, line));
+        match command {
+            "help" => println!("{}", USAGE),
+            "quit" | "exit" => return Ok(false),
+            "join" => self.send_tcp(&ClientEvent::Join.into_response()).await?,
+            "leave" => self.send_tcp(&ClientEvent::Leave.into_response()).await?,
+            "move" => {
+                let mut coordinates = args.split_whitespace().map(str::parse::<f32>);
+                let (Some(Ok(x)), Some(Ok(y))) = (coordinates.next(), coordinates.next()) else {
+                    return Err(invalid("expected move <x> <y>"));
+                };
+                let event = ClientEvent::PosUpdate(PosUpdateEvent {
+                    client_id: self.client_id()?,
+                    x,
+                    y,
+                    input: InputState::default(),
+                });
+                self.send_udp(&event.into_response()).await?;
+            }
+            "chat" => {
+                let event = ClientEvent::Chat(ChatMessageEvent {
+                    message: args.to_string(),
+                });
+                self.send_tcp(&event.into_response()).await?;
+            }
+            "emote" => {
+                let emote = match args.to_lowercase().as_str() {
+                    "hello" => Emote::Hello,
+                    "laugh" => Emote::Laugh,
+                    "angry" => Emote::Angry,
+                    "gg" => Emote::GoodGame,
+                    _ => return Err(invalid("expected hello, laugh, angry or gg")),
+                };
+                let event = ClientEvent::Emote(EmoteEvent {
+                    client_id: self.client_id()?,
+                    emote,
+                });
+                self.send_udp(&event.into_response()).await?;
+            }
+            "ping" => {
+                let event = ClientEvent::Ping(PingEvent {
+                    sent_at: self.started.elapsed().as_millis() as u64,
+                });
+                self.send_udp(&event.into_response()).await?;
+            }
+            "upgrade" => self.udp_upgrade().await?,
+            "tcp" => self.send_tcp(&format!("{}\n", args)).await?,
+            "udp" => self.send_udp(args).await?,
+            "wait" => {
+                let ms = args.parse().map_err(|_| invalid("expected wait <ms>"))?;
+                self.wait_until(Instant::now() + Duration::from_millis(ms), None)
+                    .await?;
+            }
+            "expect" => {
+                let mut args = args.split_whitespace();
+                let Some(event) = args.next() else {
+                    return Err(invalid("expected expect <event> [ms]"));
+                };
+                let timeout = match args.next() {
+                    Some(ms) => {
+                        Duration::from_millis(ms.parse().map_err(|_| invalid("invalid timeout"))?)
+                    }
+                    None => DEFAULT_EXPECT_TIMEOUT,
+                };
+                if !self
+                    .wait_until(Instant::now() + timeout, Some(event))
+                    .await?
+                {
+                    return Err(Error::Expect(event.to_string()));
+                }
+            }
+            _ => return Err(invalid("unknown command, try `help`")),
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,61 @@
+//! Tools to poke a running server from the command line:
+//!
+//! - `client [--script <file>]` connects a single client driven by typed,
+//!   piped or scripted commands, see [`cli`]
+//! - `client swarm [...]` load tests the server with many bots, see [`swarm`]
+
+use std::fmt::Display;
+
+#[path = "../args.rs"]
+mod args;
+mod cli;
+mod swarm;
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+
+    if std::env::args().nth(1).as_deref() == Some("swarm") {
+        swarm::run().await;
+    } else if let Err(e) = cli::run().await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+#[derive(Debug)]
+enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Disconnected,
+    /// The bot never got into a game before the end of the test
+    NoGame,
+    InvalidCommand(String),
+    /// No event of this type came in time
+    Expect(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+            Error::Disconnected => write!(f, "Disconnected by the server"),
+            Error::NoGame => write!(f, "No game started before the end of the test"),
+            Error::InvalidCommand(e) => write!(f, "Invalid command: {}", e),
+            Error::Expect(event) => write!(f, "Timed out waiting for {}", event),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
@@ -0,0 +1,311 @@
+//! Load generator spawning simulated clients against a running server. Each bot
+//! joins a lobby, upgrades to UDP once its game starts and then moves in a
+//! circle until the test ends:
+//!
+//! ```text
+//! client swarm --clients 50 --rate 20 --duration 30
+//! ```
+//!
+//! Bots send with their positions the time they were sent, kept in memory, so
+//! the position relays other bots receive give the end-to-end latency.
+
+use crate::args::arg_value;
+use crate::Error;
+use it_core::{
+    ClientEvent, ClientId, InputState, IntoResponse, PosUpdateEvent, ServerEvent, UdpUpgradeEvent,
+};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::Instant;
+use tracing::{error, info};
+
+/// Sent positions kept per bot to match relays against
+const SENT_POSITIONS: usize = 200;
+const CIRCLE_RADIUS: f32 = 200.0;
+
+struct Config {
+    clients: usize,
+    /// Position updates per second of each bot
+    rate: f64,
+    duration: Duration,
+    /// Delay between two bots connecting
+    ramp: Duration,
+    server_tcp: String,
+    server_udp: String,
+}
+
+impl Config {
+    fn from_args() -> Self {
+        let parse = |flag, default: f64| {
+            arg_value(flag)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            clients: parse("--clients", 10.0) as usize,
+            rate: parse("--rate", 20.0).max(0.1),
+            duration: Duration::from_secs_f64(parse("--duration", 30.0)),
+            ramp: Duration::from_millis(parse("--ramp-ms", 10.0) as u64),
+            server_tcp: arg_value("--server-tcp").unwrap_or_else(|| "127.0.0.1:8080".to_string()),
+            server_udp: arg_value("--server-udp").unwrap_or_else(|| "127.0.0.1:8081".to_string()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Metrics {
+    /// From connecting to the server accepting the join
+    join_ms: Vec<f64>,
+    /// From connecting to the game starting
+    start_ms: Vec<f64>,
+    /// From a bot sending its position to another bot receiving it
+    relay_ms: Vec<f64>,
+    positions_sent: u64,
+    relays_received: u64,
+    /// Relays that didn't match any sent position
+    unmatched_relays: u64,
+    errors: BTreeMap<String, u64>,
+}
+
+type SentPosition = (f32, f32, Instant);
+
+/// State shared by every bot
+#[derive(Default)]
+struct Swarm {
+    metrics: Mutex<Metrics>,
+    /// Recent positions of each bot with when they were sent
+    sent_positions: Mutex<HashMap<ClientId, VecDeque<SentPosition>>>,
+}
+
+impl Swarm {
+    fn record_error(&self, error: &Error) {
+        *self
+            .metrics
+            .lock()
+            .unwrap()
+            .errors
+            .entry(error.to_string())
+            .or_default() += 1;
+    }
+
+    fn record_sent(&self, client_id: &ClientId, x: f32, y: f32) {
+        let mut sent_positions = self.sent_positions.lock().unwrap();
+        let sent = sent_positions.entry(client_id.clone()).or_default();
+        if sent.len() == SENT_POSITIONS {
+            sent.pop_front();
+        }
+        sent.push_back((x, y, Instant::now()));
+        self.metrics.lock().unwrap().positions_sent += 1;
+    }
+
+    fn record_relay(&self, event: &PosUpdateEvent) {
+        let sent_at = self
+            .sent_positions
+            .lock()
+            .unwrap()
+            .get(&event.client_id)
+            .and_then(|sent| {
+                sent.iter()
+                    .rev()
+                    .find(|(x, y, _)| *x == event.x && *y == event.y)
+                    .map(|(_, _, sent_at)| *sent_at)
+            });
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics.relays_received += 1;
+        match sent_at {
+            Some(sent_at) => metrics.relay_ms.push(millis(sent_at.elapsed())),
+            None => metrics.unmatched_relays += 1,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+pub async fn run() {
+    let config = Arc::new(Config::from_args());
+    let swarm = Arc::new(Swarm::default());
+    info!(
+        "Starting {} bots sending {} updates/s for {:?}",
+        config.clients, config.rate, config.duration
+    );
+
+    let started = Instant::now();
+    let deadline = started + config.ramp * config.clients as u32 + config.duration;
+    let ramp = config.ramp;
+    let mut bots = Vec::with_capacity(config.clients);
+    for index in 0..config.clients {
+        let config = config.clone();
+        let swarm = swarm.clone();
+        bots.push(tokio::spawn(async move {
+            if let Err(e) = run_bot(index, &config, &swarm, deadline).await {
+                error!("Bot {} failed: {}", index, e);
+                swarm.record_error(&e);
+            }
+        }));
+        tokio::time::sleep(ramp).await;
+    }
+    for bot in bots {
+        let _ = bot.await;
+    }
+
+    report(&config, &swarm.metrics.lock().unwrap(), started.elapsed());
+}
+
+async fn next_event(lines: &mut Lines<BufReader<OwnedReadHalf>>) -> Result<ServerEvent, Error> {
+    let line = lines.next_line().await?.ok_or(Error::Disconnected)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// Full lifecycle of one simulated client, until `deadline`
+async fn run_bot(
+    index: usize,
+    config: &Config,
+    swarm: &Swarm,
+    deadline: Instant,
+) -> Result<(), Error> {
+    let connected_at = Instant::now();
+    let stream = TcpStream::connect(&config.server_tcp).await?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer
+        .write_all(ClientEvent::Join.into_response().as_bytes())
+        .await?;
+
+    let wait_for_start = async {
+        loop {
+            match next_event(&mut lines).await? {
+                ServerEvent::Accept(_) => {
+                    let join_ms = millis(connected_at.elapsed());
+                    swarm.metrics.lock().unwrap().join_ms.push(join_ms);
+                }
+                ServerEvent::Start(start) => return Ok::<_, Error>(start.client_id),
+                _ => {}
+            }
+        }
+    };
+    let client_id = tokio::time::timeout_at(deadline, wait_for_start)
+        .await
+        .map_err(|_| Error::NoGame)??;
+    let start_ms = millis(connected_at.elapsed());
+    swarm.metrics.lock().unwrap().start_ms.push(start_ms);
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(&config.server_udp).await?;
+
+    // Only keeps the connection drained, the server already knows we're alive
+    let tcp_reader = async {
+        while next_event(&mut lines).await.is_ok() {}
+        Err::<(), _>(Error::Disconnected)
+    };
+
+    let udp_reader = async {
+        let mut buf = [0u8; 1024];
+        loop {
+            let len = match socket.recv(&mut buf).await {
+                Ok(len) => len,
+                Err(e) => return Err::<(), _>(Error::Io(e)),
+            };
+            match serde_json::from_slice::<ServerEvent>(&buf[..len]) {
+                Ok(ServerEvent::PosUpdate(event)) => swarm.record_relay(&event),
+                Ok(_) => {}
+                Err(e) => swarm.record_error(&Error::Json(e)),
+            }
+        }
+    };
+
+    let mover = async {
+        let upgrade = ClientEvent::UdpUpgrade(UdpUpgradeEvent {
+            client_id: client_id.clone(),
+        })
+        .into_response();
+        // Resent every second in case one gets lost
+        let upgrade_every = config.rate.ceil() as u32;
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / config.rate));
+        let mut step = 0u32;
+        while Instant::now() < deadline {
+            interval.tick().await;
+            if step.is_multiple_of(upgrade_every) {
+                socket.send(upgrade.as_bytes()).await?;
+            }
+            // Bots start at different angles so they keep running into each other
+            let angle = index as f32 + step as f32 * 0.05;
+            let (x, y) = (CIRCLE_RADIUS * angle.cos(), CIRCLE_RADIUS * angle.sin());
+            let event = ClientEvent::PosUpdate(PosUpdateEvent {
+                client_id: client_id.clone(),
+                x,
+                y,
+                input: InputState {
+                    move_x: -angle.sin(),
+                    move_y: angle.cos(),
+                    ..InputState::default()
+                },
+            });
+            swarm.record_sent(&client_id, x, y);
+            socket.send(event.into_response().as_bytes()).await?;
+            step += 1;
+        }
+        Ok::<(), Error>(())
+    };
+
+    tokio::select! {
+        result = mover => result?,
+        result = tcp_reader => result?,
+        result = udp_reader => result?,
+    }
+
+    writer
+        .write_all(ClientEvent::Leave.into_response().as_bytes())
+        .await?;
+    Ok(())
+}
+
+/// Value below which `percent` of the sorted `values` fall
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = (percent / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+    sorted[index]
+}
+
+fn print_latencies(name: &str, values: &[f64]) {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    println!(
+        "{:<16} n={:<7} p50={:>8.1}ms p90={:>8.1}ms p99={:>8.1}ms max={:>8.1}ms",
+        name,
+        sorted.len(),
+        percentile(&sorted, 50.0),
+        percentile(&sorted, 90.0),
+        percentile(&sorted, 99.0),
+        sorted.last().copied().unwrap_or(0.0),
+    );
+}
+
+fn report(config: &Config, metrics: &Metrics, elapsed: Duration) {
+    println!();
+    println!(
+        "{} bots, {} updates/s each, ran for {:.1}s",
+        config.clients,
+        config.rate,
+        elapsed.as_secs_f64()
+    );
+    print_latencies("Join", &metrics.join_ms);
+    print_latencies("Game start", &metrics.start_ms);
+    print_latencies("Position relay", &metrics.relay_ms);
+    println!(
+        "Positions sent: {}, relays received: {} ({} unmatched)",
+        metrics.positions_sent, metrics.relays_received, metrics.unmatched_relays
+    );
+    let error_count: u64 = metrics.errors.values().sum();
+    println!("Errors: {}", error_count);
+    for (error, count) in &metrics.errors {
+        println!("  {:>6} {}", count, error);
+    }
+}
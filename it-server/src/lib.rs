@@ -0,0 +1,863 @@
+//! Game server keeping track of lobbies and relaying players to each other.
+//! Clients talk to it with newline-delimited JSON events over TCP, positions
+//! and other frequent updates go over UDP.
+
+use it_core::transport::{Listener, ReliableReceiver, ReliableSender, UnreliableSocket};
+use it_core::{
+    AcceptEvent, ChatEvent, ChatMessageEvent, ClientEvent, ClientId, Emote, EmoteEvent,
+    IntoResponse, LeaveEvent, LobbyId, Map, MapSource, PingEvent, Player, PlayerScore,
+    PosUpdateEvent, Position, RoundStartEvent, ScoreboardEvent, ServerEvent, StartEvent, TagEvent,
+    UdpUpgradeEvent, EMOTE_COOLDOWN, MAX_CHAT_LENGTH, TAG_COOLDOWN,
+};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+struct ServerState {
+    lobbies: HashMap<LobbyId, Vec<Player>>,
+    tcp_clients: HashMap<ClientId, mpsc::UnboundedSender<String>>,
+    udp_tx: mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>,
+    udp_client_addrs: HashMap<ClientId, SocketAddr>,
+    rounds: HashMap<LobbyId, Round>,
+    /// Last measured round trip time per client, in milliseconds
+    pings: HashMap<ClientId, u32>,
+    started: Instant,
+    /// Maps lobbies get picked from
+    maps: Vec<Map>,
+    lobby_maps: HashMap<LobbyId, LobbyMap>,
+    /// Send times of each client's recent chat messages
+    chat_history: HashMap<ClientId, VecDeque<Instant>>,
+    last_emotes: HashMap<ClientId, Instant>,
+    /// Time between a lobby filling up and its game starting
+    start_delay: Duration,
+    /// Rounds being played, one per lobby
+    round_tasks: JoinSet<()>,
+}
+
+impl ServerState {
+    fn new(
+        udp_tx: mpsc::UnboundedSender<(SocketAddr, Vec<u8>)>,
+        maps: Vec<Map>,
+        start_delay: Duration,
+    ) -> Self {
+        Self {
+            lobbies: HashMap::new(),
+            udp_tx,
+            tcp_clients: HashMap::new(),
+            udp_client_addrs: HashMap::new(),
+            rounds: HashMap::new(),
+            pings: HashMap::new(),
+            started: Instant::now(),
+            maps,
+            lobby_maps: HashMap::new(),
+            chat_history: HashMap::new(),
+            last_emotes: HashMap::new(),
+            start_delay,
+            round_tasks: JoinSet::new(),
+        }
+    }
+}
+
+struct LobbyMap {
+    /// What gets sent to clients
+    source: MapSource,
+    map: Map,
+}
+
+/// Game state of a lobby that has started playing
+struct Round {
+    /// `None` between rounds
+    it: Option<ClientId>,
+    last_tag: Instant,
+}
+
+const MAX_LOBBY_SIZE: usize = 2;
+/// Default directory maps are loaded from, relative to the working directory
+const MAPS_DIR: &str = "maps";
+/// Default time between a lobby filling up and its game starting
+const START_DELAY: Duration = Duration::from_secs(2);
+const ROUND_DURATION: Duration = Duration::from_secs(120);
+const ROUND_BREAK: Duration = Duration::from_secs(5);
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+/// Distance under which the 'it' player tags another player
+const TAG_DISTANCE: f32 = 24.0;
+/// Chat messages a client can send within `CHAT_RATE_WINDOW`
+const CHAT_RATE_LIMIT: usize = 5;
+const CHAT_RATE_WINDOW: Duration = Duration::from_secs(5);
+
+fn nickname_for(client_id: &ClientId) -> String {
+    format!("Player-{}", &client_id[..client_id.len().min(8)])
+}
+
+fn chat_notice(message: String) -> ServerEvent {
+    ServerEvent::Chat(ChatEvent {
+        client_id: None,
+        nickname: "Server".to_string(),
+        message,
+    })
+}
+
+impl ServerState {
+    fn remove_from_lobby(&mut self, client_id: &ClientId) -> Option<(LobbyId, ClientId)> {
+        if let Some((lobby_id, clients)) = self
+            .lobbies
+            .iter_mut()
+            .find(|(_, clients)| clients.iter().any(|p| p.id == *client_id))
+        {
+            clients.retain(|p| p.id != *client_id);
+            info!("Client {} removed from lobby {}", client_id, lobby_id);
+
+            return Some((lobby_id.clone(), client_id.clone()));
+        }
+        None
+    }
+    /// Removes the client from its lobby, letting the other players know
+    fn leave_lobby(&mut self, client_id: &ClientId) {
+        let removed = self.remove_from_lobby(client_id);
+        if let Some((lobby_id, client_id)) = removed {
+            let lobby = self.lobbies.get(&lobby_id).unwrap();
+
+            self.broadcast(&lobby_id, ServerEvent::Leave(LeaveEvent { client_id }));
+
+            if lobby.is_empty() {
+                self.lobbies.remove(&lobby_id);
+                self.lobby_maps.remove(&lobby_id);
+                info!("Lobby {} removed as it's empty", lobby_id);
+            }
+        }
+    }
+    /// Relays a chat message to the client's lobby, or tells the client why it was dropped
+    fn chat(&mut self, client_id: &ClientId, message: &str) {
+        let message = message.trim();
+        if message.is_empty() {
+            return;
+        }
+        let Some(lobby_id) = self.lobby_of(client_id) else {
+            return;
+        };
+        if message.chars().count() > MAX_CHAT_LENGTH {
+            let notice = format!("Message is too long (max {} characters)", MAX_CHAT_LENGTH);
+            self.send(client_id, chat_notice(notice));
+            return;
+        }
+        let now = Instant::now();
+        let history = self.chat_history.entry(client_id.clone()).or_default();
+        while history
+            .front()
+            .is_some_and(|sent_at| now.duration_since(*sent_at) > CHAT_RATE_WINDOW)
+        {
+            history.pop_front();
+        }
+        if history.len() >= CHAT_RATE_LIMIT {
+            self.send(
+                client_id,
+                chat_notice("You are sending messages too fast".to_string()),
+            );
+            return;
+        }
+        history.push_back(now);
+
+        let nickname = self
+            .lobbies
+            .get(&lobby_id)
+            .and_then(|players| players.iter().find(|p| p.id == *client_id))
+            .map_or_else(|| nickname_for(client_id), |p| p.nickname.clone());
+        let message = message.chars().filter(|c| !c.is_control()).collect();
+        self.broadcast(
+            &lobby_id,
+            ServerEvent::Chat(ChatEvent {
+                client_id: Some(client_id.clone()),
+                nickname,
+                message,
+            }),
+        );
+    }
+    /// Relays an emote to the client's lobby unless it is still on cooldown
+    fn emote(&mut self, client_id: &ClientId, emote: Emote) {
+        let Some(lobby_id) = self.lobby_of(client_id) else {
+            return;
+        };
+        let now = Instant::now();
+        if let Some(last_emote) = self.last_emotes.get(client_id) {
+            if now.duration_since(*last_emote) < EMOTE_COOLDOWN {
+                return;
+            }
+        }
+        self.last_emotes.insert(client_id.clone(), now);
+        let event = ServerEvent::Emote(EmoteEvent {
+            client_id: client_id.clone(),
+            emote,
+        });
+        self.broadcast_udp(&lobby_id, None, &event.into_response());
+    }
+    fn lobby_of(&self, client_id: &ClientId) -> Option<LobbyId> {
+        self.lobbies
+            .iter()
+            .find(|(_, players)| players.iter().any(|p| p.id == *client_id))
+            .map(|(lobby_id, _)| lobby_id.clone())
+    }
+    fn is_full(&self, lobby_id: &LobbyId) -> bool {
+        self.lobbies
+            .get(lobby_id)
+            .is_some_and(|players| players.len() >= MAX_LOBBY_SIZE)
+    }
+    fn send(&self, client_id: &ClientId, event: impl IntoResponse) {
+        if let Some(client_tx) = self.tcp_clients.get(client_id) {
+            client_tx.send(event.into_response()).unwrap_or(());
+        }
+    }
+    fn broadcast(&self, lobby_id: &LobbyId, event: impl IntoResponse) {
+        let event = event.into_response();
+        if let Some(clients) = self.lobbies.get(lobby_id) {
+            for client in clients {
+                if let Some(client_tx) = self.tcp_clients.get(&client.id) {
+                    client_tx.send(event.clone()).unwrap_or(());
+                }
+            }
+        }
+    }
+    /// Sends to every client of the lobby over UDP, except `except` when set
+    fn broadcast_udp(&self, lobby_id: &LobbyId, except: Option<&ClientId>, msg: &str) {
+        info!("Broadcasting to lobby {}: {}", lobby_id, msg);
+        if let Some(clients) = self.lobbies.get(lobby_id) {
+            for client in clients {
+                if except == Some(&client.id) {
+                    continue;
+                }
+                if let Some(socket_addr) = self.udp_client_addrs.get(&client.id) {
+                    self.udp_tx
+                        .send((*socket_addr, msg.as_bytes().to_vec()))
+                        .unwrap_or(());
+                }
+            }
+        }
+    }
+    fn assign_to_lobby(&mut self, client_id: &ClientId) -> Result<LobbyId, Error> {
+        for (lobby_id, lobby) in self.lobbies.iter_mut() {
+            if lobby.len() < MAX_LOBBY_SIZE {
+                let player = Player {
+                    id: client_id.clone(),
+                    nickname: nickname_for(client_id),
+                    it_count: 0,
+                    position: Position { x: 0.0, y: 0.0 },
+                };
+                lobby.push(player);
+                return Ok(lobby_id.clone());
+            }
+        }
+        let new_lobby_id = uuid::Uuid::new_v4().to_string();
+        let player = Player {
+            id: client_id.clone(),
+            nickname: nickname_for(client_id),
+            it_count: 0,
+            position: Position { x: 0.0, y: 0.0 },
+        };
+        self.lobbies.insert(new_lobby_id.clone(), vec![player]);
+
+        // Handcrafted maps are in the rotation along with a generated one
+        let mut rng = rand::thread_rng();
+        let source = match self.maps.get(rng.gen_range(0..=self.maps.len())) {
+            Some(map) => MapSource::Map(map.clone()),
+            None => MapSource::Seed(rng.gen()),
+        };
+        let map = source.resolve();
+        info!("Lobby {} will play on map {}", new_lobby_id, map.name);
+        self.lobby_maps
+            .insert(new_lobby_id.clone(), LobbyMap { source, map });
+
+        Ok(new_lobby_id)
+    }
+    /// Moves every player of the lobby to a distinct spawn point of its map. Maps
+    /// from the maps directory have at least one per player, players share them
+    /// on maps given through [`Server::with_maps`] that have fewer
+    fn assign_spawn_points(&mut self, lobby_id: &LobbyId) {
+        let (Some(players), Some(lobby_map)) = (
+            self.lobbies.get_mut(lobby_id),
+            self.lobby_maps.get(lobby_id),
+        ) else {
+            return;
+        };
+        // Spawn points are listed best spread first, only shuffle who gets which
+        let mut spawn_points: Vec<_> = lobby_map
+            .map
+            .spawn_points
+            .iter()
+            .take(players.len())
+            .cloned()
+            .collect();
+        spawn_points.shuffle(&mut rand::thread_rng());
+        for (player, spawn_point) in players.iter_mut().zip(spawn_points.into_iter().cycle()) {
+            player.position = spawn_point;
+        }
+    }
+    fn scoreboard(&self, lobby_id: &LobbyId) -> ScoreboardEvent {
+        let players = self
+            .lobbies
+            .get(lobby_id)
+            .map(|players| {
+                players
+                    .iter()
+                    .map(|p| PlayerScore {
+                        client_id: p.id.clone(),
+                        nickname: p.nickname.clone(),
+                        it_count: p.it_count,
+                        ping_ms: self.pings.get(&p.id).copied(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        ScoreboardEvent { players }
+    }
+    /// Picks a random 'it' player for the lobby, returns `None` if there aren't enough players
+    fn start_round(&mut self, lobby_id: &LobbyId) -> Option<RoundStartEvent> {
+        let players = self.lobbies.get_mut(lobby_id)?;
+        if players.len() < 2 {
+            return None;
+        }
+        let it = players.choose_mut(&mut rand::thread_rng())?;
+        it.it_count += 1;
+        let it = it.id.clone();
+
+        let round = self.rounds.get_mut(lobby_id)?;
+        round.it = Some(it.clone());
+        round.last_tag = Instant::now();
+
+        Some(RoundStartEvent {
+            it,
+            duration_secs: ROUND_DURATION.as_secs(),
+        })
+    }
+    fn end_round(&mut self, lobby_id: &LobbyId) {
+        if let Some(round) = self.rounds.get_mut(lobby_id) {
+            round.it = None;
+        }
+    }
+    /// Stores the new position of a player and checks whether the 'it' player tagged someone
+    fn update_position(
+        &mut self,
+        client_id: &ClientId,
+        position: Position,
+    ) -> Option<(LobbyId, TagEvent)> {
+        let (lobby_id, players) = self
+            .lobbies
+            .iter_mut()
+            .find(|(_, players)| players.iter().any(|p| p.id == *client_id))?;
+        let bounds = self.lobby_maps.get(lobby_id)?.map.bounds;
+        if let Some(player) = players.iter_mut().find(|p| p.id == *client_id) {
+            player.position = bounds.wrap(&position);
+        }
+
+        let round = self.rounds.get_mut(lobby_id)?;
+        let it = round.it.clone()?;
+        if round.last_tag.elapsed() < TAG_COOLDOWN {
+            return None;
+        }
+        let it_position = players.iter().find(|p| p.id == it)?.position.clone();
+        let tagged = players
+            .iter_mut()
+            .find(|p| p.id != it && bounds.distance(&p.position, &it_position) < TAG_DISTANCE)?;
+        tagged.it_count += 1;
+        round.it = Some(tagged.id.clone());
+        round.last_tag = Instant::now();
+        info!("Player {} tagged {} in lobby {}", it, tagged.id, lobby_id);
+
+        Some((
+            lobby_id.clone(),
+            TagEvent {
+                it: tagged.id.clone(),
+                tagged_by: it,
+            },
+        ))
+    }
+}
+
+/// Plays rounds back to back for as long as the lobby has enough players
+async fn run_rounds(state: Arc<RwLock<ServerState>>, lobby_id: LobbyId) {
+    loop {
+        {
+            let mut state = state.write().await;
+            let Some(round_start) = state.start_round(&lobby_id) else {
+                break;
+            };
+            info!(
+                "Round started in lobby {}, {} is it",
+                lobby_id, round_start.it
+            );
+            state.broadcast(&lobby_id, ServerEvent::RoundStart(round_start));
+        }
+        tokio::time::sleep(ROUND_DURATION).await;
+        {
+            let mut state = state.write().await;
+            state.end_round(&lobby_id);
+            let scoreboard = state.scoreboard(&lobby_id);
+            state.broadcast(&lobby_id, ServerEvent::RoundEnd(scoreboard));
+        }
+        tokio::time::sleep(ROUND_BREAK).await;
+    }
+    state.write().await.rounds.remove(&lobby_id);
+    info!("Stopped rounds in lobby {}", lobby_id);
+}
+
+fn load_maps(dir: impl AsRef<Path>) -> Vec<Map> {
+    let entries = match std::fs::read_dir(dir.as_ref()) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to read maps from {}: {}", dir.as_ref().display(), e);
+            return Vec::new();
+        }
+    };
+    let mut maps = Vec::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        match Map::load(&path) {
+            Ok(map) if map.spawn_points.len() < MAX_LOBBY_SIZE => error!(
+                "Skipping map {}: it has {} spawn points, lobbies need {}",
+                path.display(),
+                map.spawn_points.len(),
+                MAX_LOBBY_SIZE
+            ),
+            Ok(map) => {
+                info!("Loaded map {} from {}", map.name, path.display());
+                maps.push(map);
+            }
+            Err(e) => error!("Failed to load map {}: {}", path.display(), e),
+        }
+    }
+    maps
+}
+
+/// Game server builder, listening on ephemeral ports of localhost unless told otherwise
+pub struct Server {
+    tcp_addr: String,
+    udp_addr: String,
+    /// Loaded from `maps_dir` when `None`
+    maps: Option<Vec<Map>>,
+    maps_dir: PathBuf,
+    start_delay: Duration,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self {
+            tcp_addr: "127.0.0.1:0".to_string(),
+            udp_addr: "127.0.0.1:0".to_string(),
+            maps: None,
+            maps_dir: PathBuf::from(MAPS_DIR),
+            start_delay: START_DELAY,
+        }
+    }
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_tcp_addr(mut self, addr: impl Into<String>) -> Self {
+        self.tcp_addr = addr.into();
+        self
+    }
+    pub fn with_udp_addr(mut self, addr: impl Into<String>) -> Self {
+        self.udp_addr = addr.into();
+        self
+    }
+    /// Maps lobbies pick from instead of the ones in the maps directory
+    pub fn with_maps(mut self, maps: Vec<Map>) -> Self {
+        self.maps = Some(maps);
+        self
+    }
+    /// Directory handcrafted maps are loaded from, `maps/` of the working directory by default
+    pub fn with_maps_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.maps_dir = dir.into();
+        self
+    }
+    /// Time between a lobby filling up and its game starting
+    pub fn with_start_delay(mut self, start_delay: Duration) -> Self {
+        self.start_delay = start_delay;
+        self
+    }
+
+    /// Binds the sockets and serves clients in the background
+    pub async fn start(self) -> Result<ServerHandle, Error> {
+        let listener = TcpListener::bind(&self.tcp_addr).await?;
+        let udp_socket = UdpSocket::bind(&self.udp_addr).await?;
+        self.serve(listener, udp_socket)
+    }
+
+    /// Serves clients in the background over the given transports instead of
+    /// binding sockets, such as a [`MemoryNetwork`](it_core::transport::MemoryNetwork)
+    pub fn serve(
+        self,
+        listener: impl Listener,
+        udp_socket: impl UnreliableSocket,
+    ) -> Result<ServerHandle, Error> {
+        let udp_socket = Arc::new(udp_socket);
+        let tcp_addr = listener.local_addr()?;
+        let udp_addr = udp_socket.local_addr()?;
+
+        let maps = self.maps.unwrap_or_else(|| load_maps(&self.maps_dir));
+        let (udp_tx, mut udp_rx) = mpsc::unbounded_channel::<(SocketAddr, Vec<u8>)>();
+        let state = Arc::new(RwLock::new(ServerState::new(
+            udp_tx,
+            maps,
+            self.start_delay,
+        )));
+
+        let mut tasks = JoinSet::new();
+
+        let udp_socket_clone = udp_socket.clone();
+        tasks.spawn(async move {
+            while let Some((addr, msg)) = udp_rx.recv().await {
+                udp_socket_clone.send_to(&msg, addr).await.unwrap_or(());
+            }
+        });
+
+        let udp_server = state.clone();
+        tasks.spawn(async move {
+            if let Err(e) = handle_udp(udp_socket, udp_server).await {
+                error!("Error handling UDP: {}", e);
+            }
+        });
+
+        let ping_server = state.clone();
+        tasks.spawn(async move {
+            let mut interval = tokio::time::interval(PING_INTERVAL);
+            loop {
+                interval.tick().await;
+                let state = ping_server.read().await;
+                let sent_at = state.started.elapsed().as_millis() as u64;
+                for client_id in state.tcp_clients.keys() {
+                    state.send(client_id, ServerEvent::Ping(PingEvent { sent_at }));
+                }
+                for lobby_id in state.rounds.keys() {
+                    state.broadcast(
+                        lobby_id,
+                        ServerEvent::Scoreboard(state.scoreboard(lobby_id)),
+                    );
+                }
+            }
+        });
+
+        tasks.spawn(accept_clients(listener, state.clone()));
+        info!("Listening on http://{}", tcp_addr);
+
+        Ok(ServerHandle {
+            tcp_addr,
+            udp_addr,
+            state,
+            tasks,
+        })
+    }
+}
+
+/// Running server, stopped when dropped
+pub struct ServerHandle {
+    tcp_addr: SocketAddr,
+    udp_addr: SocketAddr,
+    state: Arc<RwLock<ServerState>>,
+    tasks: JoinSet<()>,
+}
+
+impl ServerHandle {
+    pub fn tcp_addr(&self) -> SocketAddr {
+        self.tcp_addr
+    }
+    pub fn udp_addr(&self) -> SocketAddr {
+        self.udp_addr
+    }
+    /// Waits for every task of the server to stop, the listener never does
+    /// on its own
+    pub async fn wait(&mut self) {
+        while self.tasks.join_next().await.is_some() {}
+    }
+    /// Stops serving, closing every connection and ending the rounds being played
+    pub async fn shutdown(mut self) {
+        self.tasks.shutdown().await;
+        let mut state = self.state.write().await;
+        state.round_tasks.abort_all();
+        state.tcp_clients.clear();
+        info!("Server on {} shut down", self.tcp_addr);
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        // Rounds hold on to the state, they would keep it alive forever
+        if let Ok(mut state) = self.state.try_write() {
+            state.round_tasks.abort_all();
+            state.tcp_clients.clear();
+        }
+    }
+}
+
+async fn accept_clients(mut listener: impl Listener, state: Arc<RwLock<ServerState>>) {
+    // Owned here so connections are dropped along with the listener
+    let mut clients = JoinSet::new();
+    loop {
+        let (sender, receiver) = match listener.accept().await {
+            Ok((sender, receiver, _)) => (sender, receiver),
+            Err(e) => {
+                error!("Failed to accept client: {}", e);
+                continue;
+            }
+        };
+        let state = state.clone();
+        clients.spawn(async move {
+            if let Err(e) = handle_client(sender, receiver, state).await {
+                error!("Error handling client: {}", e);
+            }
+        });
+        // Forgets the connections that already closed
+        while clients.try_join_next().is_some() {}
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+async fn handle_client(
+    mut sender: impl ReliableSender,
+    mut receiver: impl ReliableReceiver,
+    state: Arc<RwLock<ServerState>>,
+) -> Result<(), Error> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let new_client_id = uuid::Uuid::new_v4().to_string();
+    state
+        .write()
+        .await
+        .tcp_clients
+        .insert(new_client_id.clone(), tx.clone());
+
+    info!("Client {} connected", new_client_id);
+
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if sender.send(&msg).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = handle_client_events(&mut receiver, &state, &new_client_id, &tx).await;
+    // Cleanup, also after a read error so no ghost player stays in the lobby
+    {
+        let mut state = state.write().await;
+        state.tcp_clients.remove(&new_client_id);
+        state.pings.remove(&new_client_id);
+        state.chat_history.remove(&new_client_id);
+        state.last_emotes.remove(&new_client_id);
+        state.leave_lobby(&new_client_id);
+    }
+    info!("Client {} disconnected", new_client_id);
+
+    result
+}
+
+/// Handles the client's events until it disconnects
+async fn handle_client_events(
+    receiver: &mut impl ReliableReceiver,
+    state: &Arc<RwLock<ServerState>>,
+    new_client_id: &ClientId,
+    tx: &mpsc::UnboundedSender<String>,
+) -> Result<(), Error> {
+    while let Some(line) = receiver.recv().await? {
+        let cmd = line.trim();
+        // A bad line only loses that line, like a bad datagram over UDP
+        let event = match serde_json::from_str::<ClientEvent>(cmd) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Invalid message from {}: {}", new_client_id, e);
+                continue;
+            }
+        };
+
+        match event {
+            ClientEvent::Join => {
+                info!("Received JOIN command");
+
+                let (lobby_id, full) = {
+                    let mut state = state.write().await;
+                    let lobby_id = state.assign_to_lobby(new_client_id)?;
+                    let accept_event = ServerEvent::Accept(AcceptEvent {
+                        lobby_id: lobby_id.clone(),
+                        client_id: new_client_id.clone(),
+                    });
+                    state.send(new_client_id, accept_event);
+                    let full = state.is_full(&lobby_id);
+                    (lobby_id, full)
+                };
+
+                if full {
+                    // Without the lock, so other lobbies keep playing meanwhile
+                    let start_delay = state.read().await.start_delay;
+                    tokio::time::sleep(start_delay).await;
+                    start_game(state, lobby_id).await;
+                } else {
+                    let event = ServerEvent::Wait;
+                    let event = event.into_response();
+                    tx.send(event).unwrap_or(());
+                }
+            }
+            ClientEvent::Leave => {
+                info!("Received LEAVE command");
+                state.write().await.leave_lobby(new_client_id);
+            }
+            ClientEvent::Chat(ChatMessageEvent { message }) => {
+                state.write().await.chat(new_client_id, &message);
+            }
+            ClientEvent::Pong(PingEvent { sent_at }) => {
+                let mut state = state.write().await;
+                let now = state.started.elapsed().as_millis() as u64;
+                let rtt = now.saturating_sub(sent_at) as u32;
+                state.pings.insert(new_client_id.clone(), rtt);
+            }
+            _ => {
+                error!("Unknown command: {}", cmd);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sends the start of the game to a full lobby and starts its rounds
+async fn start_game(state_handle: &Arc<RwLock<ServerState>>, lobby_id: LobbyId) {
+    let mut state = state_handle.write().await;
+    // Players may have left during the start delay
+    if !state.is_full(&lobby_id) {
+        return;
+    }
+    state.assign_spawn_points(&lobby_id);
+    let map = state
+        .lobby_maps
+        .get(&lobby_id)
+        .map(|lobby_map| lobby_map.source.clone())
+        .unwrap_or(MapSource::Map(Map::default()));
+    let players = state.lobbies.get(&lobby_id).unwrap().clone();
+
+    for player in &players {
+        let event = ServerEvent::Start(StartEvent {
+            lobby_id: lobby_id.clone(),
+            client_id: player.id.clone(),
+            players: players.to_vec(),
+            map: map.clone(),
+        });
+        state.send(&player.id, event);
+    }
+
+    // A player refilling the lobby joins the rounds already running
+    if !state.rounds.contains_key(&lobby_id) {
+        state.rounds.insert(
+            lobby_id.clone(),
+            Round {
+                it: None,
+                last_tag: Instant::now(),
+            },
+        );
+        state
+            .round_tasks
+            .spawn(run_rounds(Arc::clone(state_handle), lobby_id));
+    }
+}
+
+async fn handle_udp(
+    socket: Arc<impl UnreliableSocket>,
+    state: Arc<RwLock<ServerState>>,
+) -> Result<(), Error> {
+    let mut buf = [0u8; 1024];
+
+    loop {
+        let (len, addr) = socket.recv_from(&mut buf).await?;
+        let msg = String::from_utf8_lossy(&buf[..len]);
+
+        // One bad datagram shouldn't stop relaying for every lobby
+        let event = match serde_json::from_str::<ClientEvent>(&msg) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Invalid UDP message from {}: {}", addr, e);
+                continue;
+            }
+        };
+
+        match event {
+            ClientEvent::UdpUpgrade(UdpUpgradeEvent { client_id }) => {
+                let mut state = state.write().await;
+                state.udp_client_addrs.insert(client_id, addr);
+            }
+            ClientEvent::PosUpdate(PosUpdateEvent {
+                client_id,
+                x,
+                y,
+                input,
+            }) => {
+                let mut state = state.write().await;
+                if let Some((lobby_id, tag)) = state.update_position(&client_id, Position { x, y })
+                {
+                    state.broadcast(&lobby_id, ServerEvent::Tag(tag));
+                }
+                if let Some(lobby_id) = state.lobby_of(&client_id) {
+                    let event = ServerEvent::PosUpdate(PosUpdateEvent {
+                        client_id: client_id.clone(),
+                        x,
+                        y,
+                        input,
+                    });
+                    state.broadcast_udp(&lobby_id, Some(&client_id), &event.into_response());
+                }
+            }
+            ClientEvent::Emote(EmoteEvent { client_id, emote }) => {
+                state.write().await.emote(&client_id, emote);
+            }
+            ClientEvent::Ping(ping_event) => {
+                let pong = ServerEvent::Pong(ping_event).into_response();
+                state
+                    .read()
+                    .await
+                    .udp_tx
+                    .send((addr, pong.into_bytes()))
+                    .unwrap_or(());
+            }
+            ClientEvent::Join
+            | ClientEvent::Leave
+            | ClientEvent::Pong(_)
+            | ClientEvent::Chat(_) => {}
+        }
+    }
+}
@@ -0,0 +1,535 @@
+//! Proxy sitting between clients and the server that makes the network worse
+//! on purpose. Run the server on other ports and point the proxy at it:
+//!
+//! ```text
+//! server --tcp 127.0.0.1:9080 --udp 127.0.0.1:9081
+//! netsim --profile mobile
+//! ```
+//!
+//! `--profile` takes one of the presets below or the path to a JSON file with
+//! a list of stages, played one after the other and looped:
+//!
+//! ```json
+//! { "stages": [
+//!     { "duration_secs": 20, "latency_ms": 30, "jitter_ms": 10 },
+//!     { "duration_secs": 5, "latency_ms": 300, "loss": 0.4, "bandwidth_kbps": 64 }
+//! ] }
+//! ```
+
+use args::arg_value;
+use it_core::{ClientEvent, ServerEvent};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{error, info};
+
+mod args;
+
+/// Packets queued longer than this behind a bandwidth limit get dropped, like
+/// a full router buffer would. Only applies to UDP
+const MAX_QUEUE_DELAY: Duration = Duration::from_millis(500);
+/// UDP flows without traffic either way for this long are closed
+const UDP_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Network conditions for a stretch of time
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Stage {
+    /// `None` keeps the stage forever
+    duration_secs: Option<f32>,
+    /// Added one way, in both directions
+    latency_ms: u64,
+    /// Random extra delay, between 0 and this
+    jitter_ms: u64,
+    /// Chance between 0 and 1 for a UDP packet to be dropped
+    loss: f64,
+    /// Bandwidth of each direction of a flow, unlimited when `None`
+    bandwidth_kbps: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Profile {
+    stages: Vec<Stage>,
+}
+
+impl Profile {
+    fn preset(name: &str) -> Option<Self> {
+        let stage = |latency_ms, jitter_ms, loss, bandwidth_kbps| Stage {
+            duration_secs: None,
+            latency_ms,
+            jitter_ms,
+            loss,
+            bandwidth_kbps,
+        };
+        let stages = match name {
+            "lan" => vec![stage(1, 0, 0.0, None)],
+            "wifi" => vec![stage(15, 10, 0.01, None)],
+            "mobile" => vec![stage(60, 40, 0.03, Some(1000))],
+            "bad" => vec![stage(150, 80, 0.1, Some(256))],
+            // Mostly fine with regular spikes of heavy loss
+            "flaky" => vec![
+                Stage {
+                    duration_secs: Some(20.0),
+                    ..stage(20, 10, 0.0, None)
+                },
+                Stage {
+                    duration_secs: Some(5.0),
+                    ..stage(300, 100, 0.4, Some(64))
+                },
+            ],
+            _ => return None,
+        };
+        Some(Self { stages })
+    }
+
+    fn load(name_or_path: &str) -> Result<Self, Error> {
+        if let Some(profile) = Self::preset(name_or_path) {
+            return Ok(profile);
+        }
+        let profile: Profile = serde_json::from_str(&std::fs::read_to_string(name_or_path)?)?;
+        if profile.stages.is_empty() {
+            return Err(Error::EmptyProfile);
+        }
+        Ok(profile)
+    }
+
+    /// Stage active after `elapsed` since the proxy started
+    fn stage_at(&self, elapsed: Duration) -> &Stage {
+        let looped: f32 = self
+            .stages
+            .iter()
+            .map(|stage| stage.duration_secs.unwrap_or(f32::INFINITY))
+            .sum();
+        let mut time = if looped.is_finite() {
+            elapsed.as_secs_f32() % looped
+        } else {
+            elapsed.as_secs_f32()
+        };
+        for stage in &self.stages {
+            match stage.duration_secs {
+                Some(duration) if time >= duration => time -= duration,
+                _ => return stage,
+            }
+        }
+        self.stages.last().unwrap()
+    }
+}
+
+/// One direction of a flow, deciding when each packet comes out
+struct Link {
+    profile: Arc<Profile>,
+    started: Instant,
+    /// When the link is done sending what is already queued
+    free_at: Instant,
+    /// Delivery time of the last packet, kept increasing for TCP
+    last_delivery: Instant,
+}
+
+impl Link {
+    fn new(profile: Arc<Profile>, started: Instant) -> Self {
+        let now = Instant::now();
+        Self {
+            profile,
+            started,
+            free_at: now,
+            last_delivery: now,
+        }
+    }
+
+    /// When a packet of `len` bytes should be delivered, `None` if it gets dropped
+    fn schedule(&mut self, len: usize, reliable: bool) -> Option<Instant> {
+        let now = Instant::now();
+        let stage = self.profile.stage_at(now - self.started);
+        let mut rng = rand::thread_rng();
+        if !reliable && rng.gen_bool(stage.loss.clamp(0.0, 1.0)) {
+            return None;
+        }
+        let start = self.free_at.max(now);
+        if !reliable && start - now > MAX_QUEUE_DELAY {
+            return None;
+        }
+        let transmit = match stage.bandwidth_kbps {
+            Some(kbps) if kbps > 0 => {
+                Duration::from_secs_f64(len as f64 * 8.0 / (kbps * 1000) as f64)
+            }
+            _ => Duration::ZERO,
+        };
+        self.free_at = start + transmit;
+        let delay = Duration::from_millis(stage.latency_ms + rng.gen_range(0..=stage.jitter_ms));
+        let mut deliver_at = self.free_at + delay;
+        if reliable {
+            deliver_at = deliver_at.max(self.last_delivery);
+            self.last_delivery = deliver_at;
+        }
+        Some(deliver_at)
+    }
+}
+
+/// Traffic going one way through a flow
+#[derive(Default)]
+struct DirectionStats {
+    packets: u64,
+    bytes: u64,
+    dropped: u64,
+    /// Packets that aren't valid events
+    undecoded: u64,
+    events: BTreeMap<&'static str, u64>,
+}
+
+impl DirectionStats {
+    fn record(&mut self, len: usize, event: Option<&'static str>, dropped: bool) {
+        self.packets += 1;
+        self.bytes += len as u64;
+        if dropped {
+            self.dropped += 1;
+        }
+        match event {
+            Some(name) => *self.events.entry(name).or_default() += 1,
+            None => self.undecoded += 1,
+        }
+    }
+}
+
+impl Display for DirectionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pkts, {} B, {} dropped, {} undecoded",
+            self.packets, self.bytes, self.dropped, self.undecoded
+        )?;
+        let events: Vec<String> = self
+            .events
+            .iter()
+            .map(|(name, count)| format!("{}: {}", name, count))
+            .collect();
+        if !events.is_empty() {
+            write!(f, " [{}]", events.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct FlowStats {
+    /// Client to server
+    up: DirectionStats,
+    /// Server to client
+    down: DirectionStats,
+}
+
+/// Stats of every open flow, keyed by protocol and client address
+#[derive(Clone, Default)]
+struct Flows(Arc<Mutex<BTreeMap<String, FlowStats>>>);
+
+impl Flows {
+    fn record(&self, flow: &str, up: bool, len: usize, event: Option<&'static str>, dropped: bool) {
+        let mut flows = self.0.lock().unwrap();
+        let stats = flows.entry(flow.to_string()).or_default();
+        let direction = if up { &mut stats.up } else { &mut stats.down };
+        direction.record(len, event, dropped);
+    }
+
+    fn log(flow: &str, stats: &FlowStats) {
+        info!("{} up: {}", flow, stats.up);
+        info!("{} down: {}", flow, stats.down);
+    }
+
+    fn log_all(&self) {
+        for (flow, stats) in self.0.lock().unwrap().iter() {
+            Self::log(flow, stats);
+        }
+    }
+
+    /// Logs the final stats of a flow and forgets it
+    fn close(&self, flow: &str) {
+        if let Some(stats) = self.0.lock().unwrap().remove(flow) {
+            info!("{} closed", flow);
+            Self::log(flow, &stats);
+        }
+    }
+}
+
+fn client_event_name(msg: &str) -> Option<&'static str> {
+    serde_json::from_str::<ClientEvent>(msg.trim())
+        .ok()
+        .map(|event| event.name())
+}
+
+fn server_event_name(msg: &str) -> Option<&'static str> {
+    serde_json::from_str::<ServerEvent>(msg.trim())
+        .ok()
+        .map(|event| event.name())
+}
+
+#[derive(Clone)]
+struct Proxy {
+    profile: Arc<Profile>,
+    started: Instant,
+    flows: Flows,
+    server_tcp: String,
+    server_udp: String,
+}
+
+impl Proxy {
+    fn link(&self) -> Link {
+        Link::new(self.profile.clone(), self.started)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt::init();
+
+    let listen_tcp = arg_value("--listen-tcp").unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let listen_udp = arg_value("--listen-udp").unwrap_or_else(|| "127.0.0.1:8081".to_string());
+    let profile_name = arg_value("--profile").unwrap_or_else(|| "mobile".to_string());
+    let report_interval = arg_value("--report-secs")
+        .and_then(|secs| secs.parse().ok())
+        .map_or(Duration::from_secs(10), Duration::from_secs);
+
+    let proxy = Proxy {
+        profile: Arc::new(Profile::load(&profile_name)?),
+        started: Instant::now(),
+        flows: Flows::default(),
+        server_tcp: arg_value("--server-tcp").unwrap_or_else(|| "127.0.0.1:9080".to_string()),
+        server_udp: arg_value("--server-udp").unwrap_or_else(|| "127.0.0.1:9081".to_string()),
+    };
+    info!("Using profile {}: {:?}", profile_name, proxy.profile.stages);
+
+    let listener = TcpListener::bind(&listen_tcp).await?;
+    let udp_socket = Arc::new(UdpSocket::bind(&listen_udp).await?);
+    info!(
+        "Proxying TCP {} -> {} and UDP {} -> {}",
+        listen_tcp, proxy.server_tcp, listen_udp, proxy.server_udp
+    );
+
+    let flows = proxy.flows.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(report_interval);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            flows.log_all();
+        }
+    });
+
+    let udp_proxy = proxy.clone();
+    tokio::spawn(async move {
+        if let Err(e) = proxy_udp(udp_socket, udp_proxy).await {
+            error!("Error proxying UDP: {}", e);
+        }
+    });
+
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let proxy = proxy.clone();
+        tokio::spawn(async move {
+            let flow = format!("tcp {}", addr);
+            info!("{} opened", flow);
+            if let Err(e) = proxy_tcp(stream, &flow, &proxy).await {
+                error!("{} failed: {}", flow, e);
+            }
+            proxy.flows.close(&flow);
+        });
+    }
+}
+
+async fn proxy_tcp(client: TcpStream, flow: &str, proxy: &Proxy) -> Result<(), Error> {
+    let server = TcpStream::connect(&proxy.server_tcp).await?;
+    let (client_reader, client_writer) = client.into_split();
+    let (server_reader, server_writer) = server.into_split();
+    // Whichever side hangs up first closes the whole flow
+    tokio::select! {
+        result = forward_tcp(client_reader, server_writer, flow, true, proxy) => result,
+        result = forward_tcp(server_reader, client_writer, flow, false, proxy) => result,
+    }
+}
+
+/// Forwards lines from `reader` to `writer` once the link lets them through
+async fn forward_tcp(
+    reader: impl tokio::io::AsyncRead + Unpin,
+    mut writer: impl tokio::io::AsyncWrite + Unpin + Send + 'static,
+    flow: &str,
+    up: bool,
+    proxy: &Proxy,
+) -> Result<(), Error> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(Instant, String)>();
+    let write_task = tokio::spawn(async move {
+        while let Some((deliver_at, line)) = rx.recv().await {
+            tokio::time::sleep_until(deliver_at).await;
+            writer.write_all(line.as_bytes()).await?;
+        }
+        writer.shutdown().await
+    });
+
+    let mut reader = BufReader::new(reader);
+    let mut link = proxy.link();
+    let mut line = String::new();
+    while reader.read_line(&mut line).await? != 0 {
+        let event = if up {
+            client_event_name(&line)
+        } else {
+            server_event_name(&line)
+        };
+        proxy.flows.record(flow, up, line.len(), event, false);
+        if let Some(deliver_at) = link.schedule(line.len(), true) {
+            let _ = tx.send((deliver_at, std::mem::take(&mut line)));
+        }
+        line.clear();
+    }
+    // Lets the writer flush what is still in flight before closing
+    drop(tx);
+    write_task.await.map_err(std::io::Error::other)??;
+    Ok(())
+}
+
+/// Socket relaying one UDP client to the server
+struct Upstream {
+    socket: Arc<UdpSocket>,
+    /// Client to server link
+    link: Link,
+    /// Last packet going either way, the flow closes once idle
+    last_seen: Instant,
+}
+
+type Upstreams = Arc<Mutex<HashMap<SocketAddr, Upstream>>>;
+
+/// Relays datagrams between clients and the server, using one upstream socket
+/// per client so the server sees each of them at a different address
+async fn proxy_udp(socket: Arc<UdpSocket>, proxy: Proxy) -> Result<(), Error> {
+    let upstreams: Upstreams = Default::default();
+    let mut buf = [0u8; 1024];
+    loop {
+        let (len, addr) = socket.recv_from(&mut buf).await?;
+        let flow = format!("udp {}", addr);
+        let packet = buf[..len].to_vec();
+        let event = client_event_name(&String::from_utf8_lossy(&packet));
+
+        let existing = upstreams
+            .lock()
+            .unwrap()
+            .get(&addr)
+            .map(|u| u.socket.clone());
+        let upstream = match existing {
+            Some(upstream) => upstream,
+            None => {
+                let upstream = UdpSocket::bind("0.0.0.0:0").await?;
+                upstream.connect(&proxy.server_udp).await?;
+                let upstream = Arc::new(upstream);
+                info!("{} opened", flow);
+                upstreams.lock().unwrap().insert(
+                    addr,
+                    Upstream {
+                        socket: upstream.clone(),
+                        link: proxy.link(),
+                        last_seen: Instant::now(),
+                    },
+                );
+                tokio::spawn(relay_udp_replies(
+                    upstream.clone(),
+                    socket.clone(),
+                    addr,
+                    flow.clone(),
+                    proxy.clone(),
+                    upstreams.clone(),
+                ));
+                upstream
+            }
+        };
+
+        let deliver_at = upstreams.lock().unwrap().get_mut(&addr).and_then(|u| {
+            // A client waiting in a lobby only talks, so this keeps its flow open
+            u.last_seen = Instant::now();
+            u.link.schedule(len, false)
+        });
+        proxy
+            .flows
+            .record(&flow, true, len, event, deliver_at.is_none());
+        if let Some(deliver_at) = deliver_at {
+            tokio::spawn(async move {
+                tokio::time::sleep_until(deliver_at).await;
+                upstream.send(&packet).await.unwrap_or(0);
+            });
+        }
+    }
+}
+
+/// Sends what the server answers on `upstream` back to the client at `addr`
+async fn relay_udp_replies(
+    upstream: Arc<UdpSocket>,
+    socket: Arc<UdpSocket>,
+    addr: SocketAddr,
+    flow: String,
+    proxy: Proxy,
+    upstreams: Upstreams,
+) {
+    let mut link = proxy.link();
+    let mut buf = [0u8; 1024];
+    let last_seen =
+        |upstreams: &Upstreams| upstreams.lock().unwrap().get(&addr).map(|u| u.last_seen);
+    while let Some(idle_since) = last_seen(&upstreams) {
+        let deadline = idle_since + UDP_IDLE_TIMEOUT;
+        let len = match tokio::time::timeout_at(deadline, upstream.recv(&mut buf)).await {
+            Ok(Ok(len)) => len,
+            Ok(Err(_)) => break,
+            // The client may have sent something since, which moves the deadline
+            Err(_) if last_seen(&upstreams).is_some_and(|seen| seen > idle_since) => continue,
+            Err(_) => break,
+        };
+        if let Some(u) = upstreams.lock().unwrap().get_mut(&addr) {
+            u.last_seen = Instant::now();
+        }
+        let packet = buf[..len].to_vec();
+        let event = server_event_name(&String::from_utf8_lossy(&packet));
+        let deliver_at = link.schedule(len, false);
+        proxy
+            .flows
+            .record(&flow, false, len, event, deliver_at.is_none());
+        if let Some(deliver_at) = deliver_at {
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep_until(deliver_at).await;
+                socket.send_to(&packet, addr).await.unwrap_or(0);
+            });
+        }
+    }
+    upstreams.lock().unwrap().remove(&addr);
+    proxy.flows.close(&flow);
+}
+
+#[derive(Debug)]
+enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    EmptyProfile,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+            Error::EmptyProfile => write!(f, "Profile has no stages"),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
@@ -0,0 +1,198 @@
+//! Scripted clients for end-to-end tests against a [`ServerHandle`]. Helpers
+//! panic instead of returning errors, with a timeout on everything that waits
+//! for the server so a broken test fails instead of hanging.
+//!
+//! Clients use real sockets by default, or a [`MemoryNetwork`] to run
+//! without ports and lose datagrams on purpose.
+
+use crate::ServerHandle;
+use it_core::transport::memory::{MemoryReceiver, MemorySender, MemorySocket};
+use it_core::transport::tokio::{TcpReceiver, TcpSender};
+use it_core::transport::{MemoryNetwork, ReliableReceiver, ReliableSender, UnreliableSocket};
+use it_core::{
+    AcceptEvent, ClientEvent, ClientId, InputState, IntoResponse, PosUpdateEvent, ServerEvent,
+    StartEvent, UdpUpgradeEvent,
+};
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Longest time to wait for the server
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn timeout<T>(what: &str, future: impl Future<Output = T>) -> T {
+    tokio::time::timeout(TIMEOUT, future)
+        .await
+        .unwrap_or_else(|_| panic!("Timed out waiting for {}", what))
+}
+
+/// Client connected over a reliable and an unreliable transport, TCP and UDP by default
+pub struct TestClient<S = TcpSender, R = TcpReceiver, U = UdpSocket> {
+    /// Known once the server accepted the join
+    pub client_id: Option<ClientId>,
+    sender: S,
+    receiver: R,
+    udp: U,
+    server_udp: SocketAddr,
+}
+
+/// Client of a server served over a [`MemoryNetwork`]
+pub type MemoryClient = TestClient<MemorySender, MemoryReceiver, MemorySocket>;
+
+impl TestClient {
+    pub async fn connect(server: &ServerHandle) -> Self {
+        let (sender, receiver) = it_core::transport::tokio::connect(server.tcp_addr())
+            .await
+            .expect("Failed to connect over TCP");
+        let udp = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind UDP socket");
+        Self {
+            client_id: None,
+            sender,
+            receiver,
+            udp,
+            server_udp: server.udp_addr(),
+        }
+    }
+}
+
+impl MemoryClient {
+    pub async fn connect_memory(network: &MemoryNetwork, server: &ServerHandle) -> Self {
+        let (sender, receiver) = network
+            .connect(server.tcp_addr())
+            .await
+            .expect("Failed to connect over the memory network");
+        let udp = network
+            .bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))
+            .expect("Failed to bind memory socket");
+        Self {
+            client_id: None,
+            sender,
+            receiver,
+            udp,
+            server_udp: server.udp_addr(),
+        }
+    }
+}
+
+impl<S: ReliableSender, R: ReliableReceiver, U: UnreliableSocket> TestClient<S, R, U> {
+    pub fn id(&self) -> ClientId {
+        self.client_id.clone().expect("Client has not joined yet")
+    }
+
+    pub async fn send(&mut self, event: ClientEvent) {
+        self.sender
+            .send(&event.into_response())
+            .await
+            .expect("Failed to send over TCP");
+    }
+
+    pub async fn send_udp(&self, event: ClientEvent) {
+        self.udp
+            .send_to(event.into_response().as_bytes(), self.server_udp)
+            .await
+            .expect("Failed to send over UDP");
+    }
+
+    /// Sends a line over TCP as is, such as one that isn't valid JSON
+    pub async fn send_raw(&mut self, line: &str) {
+        self.sender
+            .send(line)
+            .await
+            .expect("Failed to send over TCP");
+    }
+
+    /// Sends a datagram as is, such as one that isn't valid JSON
+    pub async fn send_udp_raw(&self, payload: &[u8]) {
+        self.udp
+            .send_to(payload, self.server_udp)
+            .await
+            .expect("Failed to send over UDP");
+    }
+
+    /// Next event sent over TCP, `None` once the server closed the connection
+    pub async fn recv(&mut self) -> Option<ServerEvent> {
+        let line = timeout("a TCP event", self.receiver.recv())
+            .await
+            .expect("Failed to read over TCP")?;
+        Some(serde_json::from_str(&line).expect("Invalid server event"))
+    }
+
+    /// Next event sent over UDP
+    pub async fn recv_udp(&self) -> ServerEvent {
+        self.try_recv_udp(TIMEOUT)
+            .await
+            .expect("Timed out waiting for a UDP event")
+    }
+
+    /// Next event sent over UDP, `None` if nothing comes within `within`, for
+    /// datagrams that may have been lost
+    pub async fn try_recv_udp(&self, within: Duration) -> Option<ServerEvent> {
+        let mut buf = [0u8; 1024];
+        let (len, _) = tokio::time::timeout(within, self.udp.recv_from(&mut buf))
+            .await
+            .ok()?
+            .expect("Failed to read over UDP");
+        Some(serde_json::from_slice(&buf[..len]).expect("Invalid server event"))
+    }
+
+    /// Skips TCP events until one with the given name, such as `Start`
+    pub async fn expect(&mut self, name: &str) -> ServerEvent {
+        loop {
+            match self.recv().await {
+                Some(event) if event.name() == name => return event,
+                Some(_) => {}
+                None => panic!("Disconnected while waiting for {}", name),
+            }
+        }
+    }
+
+    /// Waits for the server to close the connection, skipping events
+    pub async fn expect_disconnect(&mut self) {
+        while self.recv().await.is_some() {}
+    }
+
+    /// Joins a lobby and upgrades to UDP once accepted
+    pub async fn join(&mut self) -> AcceptEvent {
+        self.send(ClientEvent::Join).await;
+        let ServerEvent::Accept(accept) = self.expect("Accept").await else {
+            unreachable!();
+        };
+        self.client_id = Some(accept.client_id.clone());
+        self.send_udp(ClientEvent::UdpUpgrade(UdpUpgradeEvent {
+            client_id: accept.client_id.clone(),
+        }))
+        .await;
+        accept
+    }
+
+    pub async fn expect_start(&mut self) -> StartEvent {
+        let ServerEvent::Start(start) = self.expect("Start").await else {
+            unreachable!();
+        };
+        start
+    }
+
+    pub async fn move_to(&self, x: f32, y: f32) {
+        self.send_udp(ClientEvent::PosUpdate(PosUpdateEvent {
+            client_id: self.id(),
+            x,
+            y,
+            input: InputState::default(),
+        }))
+        .await;
+    }
+}
+
+/// Connects `count` clients and joins them one after the other
+pub async fn join_clients(server: &ServerHandle, count: usize) -> Vec<TestClient> {
+    let mut clients = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut client = TestClient::connect(server).await;
+        client.join().await;
+        clients.push(client);
+    }
+    clients
+}
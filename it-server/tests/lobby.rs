@@ -0,0 +1,144 @@
+use it_core::{ChatMessageEvent, ClientEvent, ServerEvent};
+use it_server::test_support::{join_clients, TestClient};
+use it_server::{Server, ServerHandle};
+use std::time::Duration;
+
+async fn start_server() -> ServerHandle {
+    Server::new()
+        .with_maps(Vec::new())
+        .with_start_delay(Duration::ZERO)
+        .start()
+        .await
+        .expect("Failed to start server")
+}
+
+#[tokio::test]
+async fn first_client_waits_for_a_second_player() {
+    let server = start_server().await;
+    let mut client = TestClient::connect(&server).await;
+    client.join().await;
+    client.expect("Wait").await;
+}
+
+#[tokio::test]
+async fn full_lobby_starts_a_game() {
+    let server = start_server().await;
+    let mut clients = join_clients(&server, 2).await;
+
+    let first = clients[0].expect_start().await;
+    let second = clients[1].expect_start().await;
+    assert_eq!(first.lobby_id, second.lobby_id);
+    assert_eq!(first.client_id, clients[0].id());
+    assert_eq!(second.client_id, clients[1].id());
+    assert_eq!(first.players.len(), 2);
+}
+
+#[tokio::test]
+async fn third_client_gets_a_new_lobby() {
+    let server = start_server().await;
+    let clients = join_clients(&server, 2).await;
+    let mut third = TestClient::connect(&server).await;
+    let accept = third.join().await;
+
+    let mut first = clients.into_iter().next().unwrap();
+    let start = first.expect_start().await;
+    assert_ne!(accept.lobby_id, start.lobby_id);
+}
+
+#[tokio::test]
+async fn leaving_notifies_the_lobby() {
+    let server = start_server().await;
+    let mut clients = join_clients(&server, 2).await;
+    clients[1].expect_start().await;
+
+    let leaving = clients[0].id();
+    clients[0].send(ClientEvent::Leave).await;
+    let ServerEvent::Leave(leave) = clients[1].expect("Leave").await else {
+        unreachable!();
+    };
+    assert_eq!(leave.client_id, leaving);
+}
+
+#[tokio::test]
+async fn disconnecting_notifies_the_lobby() {
+    let server = start_server().await;
+    let mut clients = join_clients(&server, 2).await;
+    clients[1].expect_start().await;
+
+    let leaving = clients.remove(0);
+    let leaving_id = leaving.id();
+    drop(leaving);
+    let ServerEvent::Leave(leave) = clients[0].expect("Leave").await else {
+        unreachable!();
+    };
+    assert_eq!(leave.client_id, leaving_id);
+}
+
+#[tokio::test]
+async fn positions_are_relayed_over_udp() {
+    let server = start_server().await;
+    let mut clients = join_clients(&server, 2).await;
+    clients[0].expect_start().await;
+
+    clients[0].move_to(12.0, 34.0).await;
+    let ServerEvent::PosUpdate(update) = clients[1].recv_udp().await else {
+        panic!("Expected a position update");
+    };
+    assert_eq!(update.client_id, clients[0].id());
+    assert_eq!((update.x, update.y), (12.0, 34.0));
+}
+
+#[tokio::test]
+async fn invalid_lines_do_not_drop_the_client() {
+    let server = start_server().await;
+    let mut first = TestClient::connect(&server).await;
+    first.send_raw("not json").await;
+    first.join().await;
+    let mut second = TestClient::connect(&server).await;
+    second.join().await;
+    first.expect_start().await;
+}
+
+#[tokio::test]
+async fn invalid_datagrams_do_not_stop_relaying() {
+    let server = start_server().await;
+    let mut clients = join_clients(&server, 2).await;
+    clients[0].expect_start().await;
+
+    clients[0].send_udp_raw(b"not json").await;
+    clients[0].send_udp_raw(&[0xff, 0xfe, 0x00]).await;
+    clients[0].move_to(12.0, 34.0).await;
+    let ServerEvent::PosUpdate(update) = clients[1].recv_udp().await else {
+        panic!("Expected a position update");
+    };
+    assert_eq!(update.client_id, clients[0].id());
+    assert_eq!((update.x, update.y), (12.0, 34.0));
+}
+
+#[tokio::test]
+async fn chat_reaches_the_whole_lobby() {
+    let server = start_server().await;
+    let mut clients = join_clients(&server, 2).await;
+
+    clients[0]
+        .send(ClientEvent::Chat(ChatMessageEvent {
+            message: "hello".to_string(),
+        }))
+        .await;
+    for client in &mut clients {
+        let ServerEvent::Chat(chat) = client.expect("Chat").await else {
+            unreachable!();
+        };
+        assert_eq!(chat.message, "hello");
+    }
+}
+
+#[tokio::test]
+async fn shutdown_closes_connections() {
+    let server = start_server().await;
+    let mut client = TestClient::connect(&server).await;
+    client.join().await;
+
+    server.shutdown().await;
+    client.expect_disconnect().await;
+}
@@ -0,0 +1,101 @@
+use it_core::transport::MemoryNetwork;
+use it_core::ServerEvent;
+use it_server::test_support::MemoryClient;
+use it_server::{Server, ServerHandle};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+/// Long enough for a datagram that wasn't lost to arrive
+const DELIVERY_TIME: Duration = Duration::from_millis(100);
+
+fn start_server(network: &MemoryNetwork) -> ServerHandle {
+    let any_port = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+    Server::new()
+        .with_maps(Vec::new())
+        .with_start_delay(Duration::ZERO)
+        .serve(
+            network.listen(any_port).expect("Failed to listen"),
+            network.bind(any_port).expect("Failed to bind"),
+        )
+        .expect("Failed to start server")
+}
+
+async fn start_game(network: &MemoryNetwork, server: &ServerHandle) -> [MemoryClient; 2] {
+    let mut first = MemoryClient::connect_memory(network, server).await;
+    first.join().await;
+    let mut second = MemoryClient::connect_memory(network, server).await;
+    second.join().await;
+    first.expect_start().await;
+    second.expect_start().await;
+    [first, second]
+}
+
+/// Moves the first client `count` times, returning the x of each position the second one got
+async fn relayed_moves(network: &MemoryNetwork, count: usize) -> Vec<f32> {
+    let server = start_server(network);
+    let [first, second] = start_game(network, &server).await;
+    // Only lose datagrams once both clients upgraded to UDP
+    network.set_loss(0.5);
+    for i in 0..count {
+        first.move_to(i as f32, 0.0).await;
+    }
+    let mut received = Vec::new();
+    while let Some(event) = second.try_recv_udp(DELIVERY_TIME).await {
+        if let ServerEvent::PosUpdate(update) = event {
+            received.push(update.x);
+        }
+    }
+    received
+}
+
+#[tokio::test]
+async fn lobby_starts_without_sockets() {
+    let network = MemoryNetwork::new();
+    let server = start_server(&network);
+    let [first, second] = start_game(&network, &server).await;
+    assert_ne!(first.id(), second.id());
+}
+
+#[tokio::test]
+async fn connecting_without_a_listener_is_refused() {
+    let network = MemoryNetwork::new();
+    let addr = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8080);
+    let error = network.connect(addr).await.err().expect("Connected");
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+}
+
+#[tokio::test]
+async fn lost_positions_are_not_relayed() {
+    let network = MemoryNetwork::new();
+    let server = start_server(&network);
+    let [first, second] = start_game(&network, &server).await;
+
+    network.set_loss(1.0);
+    first.move_to(10.0, 20.0).await;
+    assert!(second.try_recv_udp(DELIVERY_TIME).await.is_none());
+
+    network.set_loss(0.0);
+    first.move_to(30.0, 40.0).await;
+    let ServerEvent::PosUpdate(update) = second.recv_udp().await else {
+        panic!("Expected a position update");
+    };
+    assert_eq!((update.x, update.y), (30.0, 40.0));
+}
+
+#[tokio::test]
+async fn same_seed_loses_the_same_datagrams() {
+    let first_run = relayed_moves(&MemoryNetwork::new().with_loss(0.0, 7), 40).await;
+    let second_run = relayed_moves(&MemoryNetwork::new().with_loss(0.0, 7), 40).await;
+    assert!(!first_run.is_empty() && first_run.len() < 40);
+    assert_eq!(first_run, second_run);
+}
+
+#[tokio::test]
+async fn shutdown_closes_memory_connections() {
+    let network = MemoryNetwork::new();
+    let server = start_server(&network);
+    let mut client = MemoryClient::connect_memory(&network, &server).await;
+    client.join().await;
+    server.shutdown().await;
+    client.expect_disconnect().await;
+}